@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+use crate::{BatteryInfo, BatteryState, Energy, EstimatedTimeRemaining, Power, Status};
+
+/// Charge level of all batteries together, in range [0, 100], weighted by their energy.
+///
+/// When the energy of any battery is unknown, falls back to the state of charge weighted by
+/// the full energy of the batteries, or to the mean state of charge.
+pub fn combined_energy_percentage(batteries: &[BatteryInfo]) -> Option<u8> {
+    if batteries.is_empty() {
+        return None;
+    }
+    let energies = batteries
+        .iter()
+        .map(|battery| Some((battery.energy?, battery.energy_full?)))
+        .collect::<Option<Vec<_>>>();
+    let (energy, energy_full) = energies.unwrap_or_default().into_iter().fold(
+        (Energy::default(), Energy::default()),
+        |(energy, energy_full), (e, f)| (energy + e, energy_full + f),
+    );
+    let capacities = batteries
+        .iter()
+        .map(|battery| battery.energy_full.filter(|energy| energy.joules() > 0.0))
+        .collect::<Option<Vec<_>>>();
+    let ratio = if energy_full.joules() > 0.0 {
+        energy.joules() / energy_full.joules()
+    } else if let Some(capacities) = capacities {
+        batteries
+            .iter()
+            .zip(&capacities)
+            .map(|(battery, capacity)| battery.state_of_charge * capacity.joules())
+            .sum::<f32>()
+            / capacities.iter().copied().sum::<Energy>().joules()
+    } else {
+        batteries
+            .iter()
+            .map(|battery| battery.state_of_charge)
+            .sum::<f32>()
+            / batteries.len() as f32
+    };
+    Some((ratio.clamp(0.0, 1.0) * 100.0).round() as u8)
+}
+
+/// Time until all batteries are empty or full, from the net energy rate of all batteries.
+///
+/// Falls back to the estimates of the individual batteries when the rates are unknown, or the
+/// energy of any battery is.
+pub fn combined_time_remaining(batteries: &[BatteryInfo]) -> Option<EstimatedTimeRemaining> {
+    // Batteries may charge and discharge at the same time, e.g. when one charges the other.
+    let (charging, discharging) = batteries.iter().fold(
+        (Power::default(), Power::default()),
+        |(charging, discharging), battery| match (battery.state, battery.energy_rate) {
+            (BatteryState::Charging, Some(rate)) => (charging + rate, discharging),
+            (BatteryState::Discharging, Some(rate)) => (charging, discharging + rate),
+            _ => (charging, discharging),
+        },
+    );
+    let net = charging.watts() - discharging.watts();
+    let energy: Option<Energy> = batteries.iter().map(|battery| battery.energy).sum();
+    if net < 0.0
+        && let Some(energy) = energy
+        && let Some(time) = energy.time_at(Power::from_watts(-net))
+    {
+        return Some(EstimatedTimeRemaining::Discharging(time));
+    }
+    let energy_full: Option<Energy> = batteries.iter().map(|battery| battery.energy_full).sum();
+    if net > 0.0
+        && let Some(energy) = energy
+        && let Some(energy_full) = energy_full
+        && let Some(time) = (energy_full - energy).time_at(Power::from_watts(net))
+    {
+        return Some(EstimatedTimeRemaining::Charging(time));
+    }
+
+    let any_in = |state| batteries.iter().any(|battery| battery.state == state);
+    // Batteries of dual-battery laptops are usually discharged one after the other, but charged
+    // at the same time.
+    if any_in(BatteryState::Discharging) {
+        let time = batteries
+            .iter()
+            .filter_map(|battery| battery.time_to_empty)
+            .sum::<Duration>();
+        (!time.is_zero()).then_some(EstimatedTimeRemaining::Discharging(time))
+    } else if any_in(BatteryState::Charging) {
+        let time = batteries
+            .iter()
+            .filter_map(|battery| battery.time_to_full)
+            .max()
+            .unwrap_or_default();
+        (!time.is_zero()).then_some(EstimatedTimeRemaining::Charging(time))
+    } else {
+        None
+    }
+}
+
+/// Computes the values of `status` that the OS didn't supply from its batteries.
+#[cfg_attr(all(target_os = "linux", not(feature = "dbus")), allow(dead_code))]
+pub(crate) fn fill_missing(status: &mut Status) {
+    if status.estimated_energy_percentage.is_none() {
+        status.estimated_energy_percentage = combined_energy_percentage(&status.batteries);
+    }
+    if status.estimated_time_remaining.is_none() {
+        status.estimated_time_remaining = combined_time_remaining(&status.batteries);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battery(state: BatteryState, energy: f32, energy_full: f32, rate: f32) -> BatteryInfo {
+        BatteryInfo {
+            state,
+            energy: Some(Energy::from_watt_hours(energy)),
+            energy_full: Some(Energy::from_watt_hours(energy_full)),
+            energy_rate: Some(Power::from_watts(rate)),
+            state_of_charge: energy / energy_full,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dual_battery() {
+        // A small internal battery kept full while the large external one discharges.
+        let batteries = [
+            battery(BatteryState::Full, 24.0, 24.0, 0.0),
+            battery(BatteryState::Discharging, 18.0, 72.0, 10.0),
+        ];
+        // The mean state of charge would be 62%.
+        assert_eq!(combined_energy_percentage(&batteries), Some(44));
+        assert_eq!(
+            combined_time_remaining(&batteries),
+            Some(EstimatedTimeRemaining::Discharging(Duration::from_secs(
+                42 * 3600 / 10
+            )))
+        );
+
+        let batteries = [
+            battery(BatteryState::Charging, 12.0, 24.0, 30.0),
+            battery(BatteryState::Discharging, 36.0, 72.0, 6.0),
+        ];
+        assert_eq!(
+            combined_time_remaining(&batteries),
+            Some(EstimatedTimeRemaining::Charging(Duration::from_secs(
+                48 * 3600 / 24
+            )))
+        );
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        assert_eq!(combined_energy_percentage(&[]), None);
+        assert_eq!(combined_time_remaining(&[]), None);
+
+        let batteries = [
+            BatteryInfo {
+                state: BatteryState::Discharging,
+                state_of_charge: 0.5,
+                time_to_empty: Some(Duration::from_secs(600)),
+                ..Default::default()
+            },
+            BatteryInfo {
+                state: BatteryState::Discharging,
+                state_of_charge: 0.3,
+                time_to_empty: Some(Duration::from_secs(300)),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(combined_energy_percentage(&batteries), Some(40));
+        assert_eq!(
+            combined_time_remaining(&batteries),
+            Some(EstimatedTimeRemaining::Discharging(Duration::from_secs(
+                900
+            )))
+        );
+
+        // Only the state of charge of the second battery is known, as well as the full energy
+        // of both.
+        let mut partial = [
+            battery(BatteryState::Full, 24.0, 24.0, 0.0),
+            BatteryInfo {
+                energy: None,
+                ..battery(BatteryState::Discharging, 18.0, 72.0, 10.0)
+            },
+        ];
+        assert_eq!(combined_energy_percentage(&partial), Some(44));
+        partial[1].energy_full = None;
+        assert_eq!(combined_energy_percentage(&partial), Some(63));
+        // The unknown energy would otherwise shorten the time to the 24 Wh of the first one.
+        assert_eq!(combined_time_remaining(&partial), None);
+        partial[1].time_to_empty = Some(Duration::from_secs(7200));
+        assert_eq!(
+            combined_time_remaining(&partial),
+            Some(EstimatedTimeRemaining::Discharging(Duration::from_secs(
+                7200
+            )))
+        );
+
+        // A rate without energy, e.g. sysfs with `power_now` but no `energy_now`.
+        let mut unknown_energy = [BatteryInfo {
+            energy: None,
+            ..battery(BatteryState::Discharging, 18.0, 72.0, 10.0)
+        }];
+        assert_eq!(combined_time_remaining(&unknown_energy), None);
+        unknown_energy[0].state = BatteryState::Charging;
+        assert_eq!(combined_time_remaining(&unknown_energy), None);
+        unknown_energy[0].energy = Some(Energy::from_watt_hours(18.0));
+        unknown_energy[0].energy_full = None;
+        assert_eq!(combined_time_remaining(&unknown_energy), None);
+
+        let mut status = Status {
+            estimated_energy_percentage: Some(41),
+            batteries: batteries.to_vec(),
+            ..Default::default()
+        };
+        fill_missing(&mut status);
+        assert_eq!(status.estimated_energy_percentage, Some(41));
+        assert!(status.estimated_time_remaining.is_some());
+        assert_eq!(status.os_time_remaining, None);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{Error, OnPowerStateChange, Status, WatchOptions};
+
+/// Environment variable naming the backend used by [`default_backend`], e.g. `upower`.
+///
+/// [`crate::get_current_power_state`] and the `register_*` functions built on it use the same
+/// backend.
+pub const BACKEND_ENV_VAR: &str = "POWERSTATE_BACKEND";
+
+bitflags::bitflags! {
+    /// What a [`PowerBackend`] is able to report.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Capabilities: u32 {
+        /// Changes are pushed by the OS rather than polled.
+        const NOTIFICATIONS = 1 << 0;
+        const TIME_REMAINING = 1 << 1;
+        const SAVER_MODE = 1 << 2;
+        /// [`Status::power_sources`] is filled.
+        const POWER_SOURCES = 1 << 3;
+        const PERIPHERAL_BATTERIES = 1 << 4;
+    }
+}
+
+/// Ends the subscription of a [`PowerBackend`] when dropped.
+pub struct BackendGuard {
+    _guard: Box<dyn Send + Sync>,
+}
+
+impl BackendGuard {
+    pub fn new<G: Send + Sync + 'static>(guard: G) -> Self {
+        BackendGuard {
+            _guard: Box::new(guard),
+        }
+    }
+}
+
+/// A source of power state, e.g. sysfs or UPower on Linux.
+///
+/// The platform backends are returned by [`backend`] and [`default_backend`]. Implement it to
+/// feed [`crate::PowerMonitor`] from another source.
+pub trait PowerBackend: Send + Sync {
+    /// The name [`backend`] selects this backend by.
+    fn name(&self) -> &str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    fn status(&self) -> Result<Status, Error>;
+
+    /// Invokes the callback for the changes selected by `options` until the guard is dropped.
+    fn subscribe(
+        &self,
+        options: WatchOptions,
+        cb: OnPowerStateChange,
+    ) -> Result<BackendGuard, Error>;
+}
+
+/// Names of the backends available on this platform, the default first.
+pub fn backend_names() -> &'static [&'static str] {
+    crate::os_impl::BACKEND_NAMES
+}
+
+/// Creates the backend with the given name, see [`backend_names`].
+pub fn backend(name: &str) -> Result<Box<dyn PowerBackend>, Error> {
+    crate::os_impl::backend(name).unwrap_or_else(|| Err(Error::UnknownBackend(name.to_string())))
+}
+
+/// Creates the backend named by the `POWERSTATE_BACKEND` environment variable, or the default
+/// backend of the platform when it is unset.
+pub fn default_backend() -> Result<Box<dyn PowerBackend>, Error> {
+    select_backend(std::env::var(BACKEND_ENV_VAR).ok().as_deref())
+}
+
+fn select_backend(name: Option<&str>) -> Result<Box<dyn PowerBackend>, Error> {
+    match name.map(str::trim) {
+        Some(name) if !name.is_empty() => backend(name),
+        _ => backend(backend_names()[0]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_backend() {
+        let default = backend_names()[0];
+        assert_eq!(select_backend(None).unwrap().name(), default);
+        assert_eq!(select_backend(Some(" ")).unwrap().name(), default);
+        assert_eq!(select_backend(Some(default)).unwrap().name(), default);
+        assert!(matches!(
+            select_backend(Some("nonexistent")),
+            Err(Error::UnknownBackend(name)) if name == "nonexistent"
+        ));
+    }
+}
@@ -1,28 +1,235 @@
-pub use starship_battery::State as BatteryState;
-pub use starship_battery::Technology as BatteryTechnology;
+use std::{fmt, time::Duration};
+
+use crate::{Energy, Power, Temperature, Voltage};
+
+/// Charging state of a battery.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum BatteryState {
+    Charging,
+    Discharging,
+    Empty,
+    Full,
+    /// Connected to power but not charging, e.g. because of a charge limit.
+    NotCharging,
+    /// Waiting to charge, e.g. until the battery has cooled down.
+    PendingCharge,
+    /// Waiting to discharge.
+    PendingDischarge,
+    /// Also used for states added by later versions when deserializing.
+    #[default]
+    #[cfg_attr(feature = "serde", serde(other))]
+    Unknown,
+}
+
+/// Chemistry of a battery.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum BatteryTechnology {
+    LithiumIon,
+    LithiumPolymer,
+    LithiumIronPhosphate,
+    LithiumManganese,
+    LeadAcid,
+    NickelMetalHydride,
+    NickelCadmium,
+    NickelZinc,
+    RechargeableAlkalineManganese,
+    /// Also used for technologies added by later versions when deserializing.
+    #[default]
+    #[cfg_attr(feature = "serde", serde(other))]
+    Unknown,
+}
+
+impl From<starship_battery::State> for BatteryState {
+    fn from(state: starship_battery::State) -> Self {
+        match state {
+            starship_battery::State::Unknown => BatteryState::Unknown,
+            starship_battery::State::Charging => BatteryState::Charging,
+            starship_battery::State::Discharging => BatteryState::Discharging,
+            starship_battery::State::Empty => BatteryState::Empty,
+            starship_battery::State::Full => BatteryState::Full,
+        }
+    }
+}
+
+impl From<starship_battery::Technology> for BatteryTechnology {
+    fn from(technology: starship_battery::Technology) -> Self {
+        use starship_battery::Technology;
+
+        match technology {
+            Technology::LithiumIon => BatteryTechnology::LithiumIon,
+            Technology::LeadAcid => BatteryTechnology::LeadAcid,
+            Technology::LithiumPolymer => BatteryTechnology::LithiumPolymer,
+            Technology::NickelMetalHydride => BatteryTechnology::NickelMetalHydride,
+            Technology::NickelCadmium => BatteryTechnology::NickelCadmium,
+            Technology::NickelZinc => BatteryTechnology::NickelZinc,
+            Technology::LithiumIronPhosphate => BatteryTechnology::LithiumIronPhosphate,
+            Technology::RechargeableAlkalineManganese => {
+                BatteryTechnology::RechargeableAlkalineManganese
+            }
+            _ => BatteryTechnology::Unknown,
+        }
+    }
+}
+
+/// Identifies a battery across readings, e.g. `BAT0` on Linux.
+///
+/// Derived from the platform path of the battery where available, e.g. the device instance path
+/// on Windows, otherwise from its vendor, model and serial number.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct BatteryId(String);
+
+impl BatteryId {
+    pub fn new(id: impl Into<String>) -> Self {
+        BatteryId(id.into())
+    }
+
+    /// For batteries without a platform path, e.g. `SMP:bq20z451:0123`.
+    ///
+    /// Batteries of the same model without a serial number get the same id.
+    #[cfg_attr(all(target_os = "linux", not(feature = "dbus")), allow(dead_code))]
+    pub(crate) fn fallback(
+        vendor: Option<&str>,
+        model: Option<&str>,
+        serial_number: Option<&str>,
+    ) -> Self {
+        let attributes = [vendor, model, serial_number]
+            .into_iter()
+            .filter_map(attribute)
+            .collect::<Vec<_>>();
+        if attributes.is_empty() {
+            BatteryId::new("unknown")
+        } else {
+            BatteryId::new(attributes.join(":"))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for BatteryId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A reported attribute of a battery, `None` when blank.
+fn attribute(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|value| !value.is_empty())
+}
+
+/// A battery as enumerated by the platform, to take the place of the fallback id of the
+/// matching [`BatteryInfo`].
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+#[derive(Debug, Default)]
+pub(crate) struct BatteryDevice {
+    pub id: BatteryId,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+impl BatteryDevice {
+    /// Whether the attributes known of this device are those of `battery`.
+    fn matches(&self, battery: &BatteryInfo) -> bool {
+        let known = [
+            (&self.vendor, &battery.vendor),
+            (&self.model, &battery.model),
+            (&self.serial_number, &battery.serial_number),
+        ]
+        .into_iter()
+        .filter_map(|(device, battery)| {
+            Some((attribute(device.as_deref())?, attribute(battery.as_deref())))
+        })
+        .collect::<Vec<_>>();
+        !known.is_empty()
+            && known
+                .iter()
+                .all(|(device, battery)| Some(*device) == *battery)
+    }
+}
+
+/// Gives each battery the id of the device with the same attributes.
+///
+/// Identical devices are taken in enumeration order. A single battery takes the id of a single
+/// device, whatever their attributes.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+pub(crate) fn assign_device_ids(batteries: &mut [BatteryInfo], mut devices: Vec<BatteryDevice>) {
+    if let ([battery], [device]) = (&mut *batteries, devices.as_slice()) {
+        battery.id = device.id.clone();
+        return;
+    }
+    for battery in batteries {
+        if let Some(index) = devices.iter().position(|device| device.matches(battery)) {
+            battery.id = devices.remove(index).id;
+        }
+    }
+}
 
 /// A newtype for battery information.
 /// Ref: https://docs.rs/starship-battery/latest/starship_battery/struct.Battery.html
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct BatteryInfo {
+    pub id: BatteryId,
+    /// Charge level, in range [0, 1].
     pub state_of_charge: f32,
-    pub energy: f32,
-    pub energy_full: f32,
-    pub energy_full_design: f32,
-    pub energy_rate: f32,
-    pub voltage: f32,
-    pub state_of_health: f32,
+    pub energy: Option<Energy>,
+    pub energy_full: Option<Energy>,
+    pub energy_full_design: Option<Energy>,
+    /// Rate of charging or discharging, always positive.
+    pub energy_rate: Option<Power>,
+    pub voltage: Option<Voltage>,
+    /// Full energy relative to the design capacity, in range [0, 1].
+    pub state_of_health: Option<f32>,
     pub state: BatteryState,
     pub technology: BatteryTechnology,
-    pub temperature: f32,
-    pub cycle_count: u32,
+    pub temperature: Option<Temperature>,
+    pub cycle_count: Option<u32>,
     pub vendor: Option<String>,
     pub model: Option<String>,
     pub serial_number: Option<String>,
-    pub time_to_full: Option<f32>,
-    pub time_to_empty: Option<f32>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::option_duration_secs")
+    )]
+    pub time_to_full: Option<Duration>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helpers::option_duration_secs")
+    )]
+    pub time_to_empty: Option<Duration>,
+}
+
+/// A value reported by starship-battery, which reports `0` for most unknown values.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+fn positive(value: f32) -> Option<f32> {
+    (value > 0.0).then_some(value)
 }
 
+#[cfg(not(target_os = "linux"))]
 pub fn get_batteries() -> Result<Vec<BatteryInfo>, starship_battery::Error> {
     let manager = starship_battery::Manager::new()?;
     let mut vc: Vec<BatteryInfo> = Vec::new();
@@ -31,23 +238,33 @@ pub fn get_batteries() -> Result<Vec<BatteryInfo>, starship_battery::Error> {
 
     for bat in iter {
         vc.push(match bat {
+            // Replaced with the platform id where it can be matched, see `assign_device_ids`.
             Ok(battery) => BatteryInfo {
+                id: BatteryId::fallback(battery.vendor(), battery.model(), battery.serial_number()),
+                // starship-battery reports SI units.
                 state_of_charge: battery.state_of_charge().value,
-                energy: battery.energy().value,
-                energy_full: battery.energy_full().value,
-                energy_full_design: battery.energy_full_design().value,
-                energy_rate: battery.energy_rate().value,
-                voltage: battery.voltage().value,
-                state_of_health: battery.state_of_health().value,
-                state: battery.state(),
-                technology: battery.technology(),
-                temperature: battery.temperature().map(|t| t.value).unwrap_or_default(),
-                cycle_count: battery.cycle_count().unwrap_or_default(),
+                energy: Some(Energy::from_joules(battery.energy().value)),
+                energy_full: positive(battery.energy_full().value).map(Energy::from_joules),
+                energy_full_design: positive(battery.energy_full_design().value)
+                    .map(Energy::from_joules),
+                energy_rate: positive(battery.energy_rate().value.abs()).map(Power::from_watts),
+                voltage: positive(battery.voltage().value).map(Voltage::from_volts),
+                state_of_health: positive(battery.state_of_health().value),
+                state: battery.state().into(),
+                technology: battery.technology().into(),
+                temperature: battery
+                    .temperature()
+                    .map(|t| Temperature::from_kelvin(t.value)),
+                cycle_count: battery.cycle_count(),
                 vendor: battery.vendor().map(|v| v.to_string()),
                 model: battery.model().map(|m| m.to_string()),
                 serial_number: battery.serial_number().map(|s| s.to_string()),
-                time_to_full: battery.time_to_full().map(|t| t.value),
-                time_to_empty: battery.time_to_empty().map(|t| t.value),
+                time_to_full: battery
+                    .time_to_full()
+                    .and_then(|t| Duration::try_from_secs_f32(t.value).ok()),
+                time_to_empty: battery
+                    .time_to_empty()
+                    .and_then(|t| Duration::try_from_secs_f32(t.value).ok()),
             },
             Err(e) => {
                 log::warn!("Unable to access battery information: {e}");
@@ -58,3 +275,138 @@ pub fn get_batteries() -> Result<Vec<BatteryInfo>, starship_battery::Error> {
 
     Ok(vc)
 }
+
+/// Get the battery with the given id, if it is still present.
+pub fn get_battery(id: &BatteryId) -> Result<Option<BatteryInfo>, crate::Error> {
+    Ok(crate::get_current_power_state()?
+        .batteries
+        .into_iter()
+        .find(|battery| &battery.id == id))
+}
+
+/// Kind of a peripheral device powered by its own battery.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+pub enum PeripheralKind {
+    Mouse,
+    Keyboard,
+    Touchpad,
+    Pen,
+    Gamepad,
+    Headset,
+    Speakers,
+    Phone,
+    Tablet,
+    #[default]
+    Unknown,
+}
+
+/// Battery of a peripheral device, e.g. a wireless mouse or a Bluetooth headset.
+///
+/// Peripheral batteries don't power the system, so they are not part of
+/// [`crate::Status::batteries`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PeripheralBattery {
+    /// Platform name of the battery, e.g. `hid-00:11:22:33:44:55-battery` on Linux.
+    pub name: String,
+    pub model: Option<String>,
+    pub kind: PeripheralKind,
+    /// Charge level, in range [0, 100].
+    pub percentage: Option<u8>,
+    pub state: BatteryState,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positive() {
+        assert_eq!(positive(0.0), None);
+        assert_eq!(positive(-1.0), None);
+        assert_eq!(positive(12.5), Some(12.5));
+    }
+
+    #[test]
+    fn test_display_from_str() {
+        assert_eq!(BatteryState::NotCharging.to_string(), "not_charging");
+        assert_eq!(
+            "Pending_Charge".parse::<BatteryState>().unwrap(),
+            BatteryState::PendingCharge
+        );
+        assert_eq!(BatteryTechnology::LithiumIon.to_string(), "lithium-ion");
+        assert_eq!(
+            "lithium-iron-phosphate"
+                .parse::<BatteryTechnology>()
+                .unwrap(),
+            BatteryTechnology::LithiumIronPhosphate
+        );
+        assert!("solid-state".parse::<BatteryTechnology>().is_err());
+    }
+
+    #[test]
+    fn test_fallback_battery_id() {
+        assert_eq!(
+            BatteryId::fallback(Some("SMP"), Some("Model "), Some("SN123")).as_str(),
+            "SMP:Model:SN123"
+        );
+        assert_eq!(
+            BatteryId::fallback(None, Some("Model"), Some("")).as_str(),
+            "Model"
+        );
+        assert_eq!(BatteryId::fallback(None, None, None).as_str(), "unknown");
+    }
+
+    #[test]
+    fn test_assign_device_ids() {
+        let battery = |serial_number: &str| BatteryInfo {
+            id: BatteryId::fallback(Some("SMP"), Some("Model"), Some(serial_number)),
+            vendor: Some("SMP".to_string()),
+            model: Some("Model".to_string()),
+            serial_number: Some(serial_number.to_string()),
+            ..Default::default()
+        };
+        let device = |id: &str, serial_number: &str| BatteryDevice {
+            id: BatteryId::new(id),
+            serial_number: Some(serial_number.to_string()),
+            ..Default::default()
+        };
+
+        // Matched by serial number whatever the order.
+        let mut batteries = [battery("B"), battery("A"), battery("C")];
+        assign_device_ids(
+            &mut batteries,
+            vec![
+                device("ACPI\\PNP0C0A\\1", "A"),
+                device("ACPI\\PNP0C0A\\2", "B"),
+            ],
+        );
+        assert_eq!(
+            batteries.map(|battery| battery.id.to_string()),
+            ["ACPI\\PNP0C0A\\2", "ACPI\\PNP0C0A\\1", "SMP:Model:C"]
+        );
+
+        let mut batteries = [battery(" ")];
+        assign_device_ids(&mut batteries, vec![device("42", "")]);
+        assert_eq!(batteries[0].id.as_str(), "42");
+    }
+
+    #[test]
+    fn test_from_starship_battery() {
+        assert_eq!(
+            BatteryState::from(starship_battery::State::Discharging),
+            BatteryState::Discharging
+        );
+        assert_eq!(
+            BatteryState::from(starship_battery::State::Unknown),
+            BatteryState::Unknown
+        );
+        assert_eq!(
+            BatteryTechnology::from(starship_battery::Technology::LithiumPolymer),
+            BatteryTechnology::LithiumPolymer
+        );
+        assert_eq!(
+            BatteryTechnology::from(starship_battery::Technology::Unknown),
+            BatteryTechnology::Unknown
+        );
+    }
+}
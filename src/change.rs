@@ -0,0 +1,350 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use crate::{BatteryId, Error, EstimatedTimeRemaining, Guard, PowerState, Status};
+
+/// Changes of the time remaining smaller than this are treated as noise.
+const TIME_REMAINING_TOLERANCE: Duration = Duration::from_secs(60);
+
+bitflags::bitflags! {
+    /// Fields of [`Status`] that differ between two readings.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ChangedFields: u32 {
+        const POWER_STATE = 1 << 0;
+        const PERCENTAGE = 1 << 1;
+        /// Only set when the OS or computed estimate moved by at least a minute, or switched
+        /// between charging and discharging.
+        const TIME_REMAINING = 1 << 2;
+        const SAVER_MODE = 1 << 3;
+        /// A battery was added, removed or replaced, as told by [`crate::BatteryId`].
+        const BATTERY_SET = 1 << 4;
+        /// A battery started or stopped charging, e.g. when it became full.
+        const BATTERY_STATE = 1 << 5;
+        /// A power source was connected, disconnected, added or removed.
+        const POWER_SOURCES = 1 << 6;
+    }
+}
+
+fn time_remaining_changed(
+    previous: Option<&EstimatedTimeRemaining>,
+    current: Option<&EstimatedTimeRemaining>,
+) -> bool {
+    match (previous, current) {
+        (None, None) => false,
+        (Some(EstimatedTimeRemaining::Charging(a)), Some(EstimatedTimeRemaining::Charging(b)))
+        | (
+            Some(EstimatedTimeRemaining::Discharging(a)),
+            Some(EstimatedTimeRemaining::Discharging(b)),
+        ) => a.abs_diff(*b) >= TIME_REMAINING_TOLERANCE,
+        _ => true,
+    }
+}
+
+fn computed_time_remaining(status: &Status) -> Option<&EstimatedTimeRemaining> {
+    status
+        .computed_time_remaining
+        .as_ref()
+        .map(|estimate| &estimate.time_remaining)
+}
+
+fn battery_ids(status: &Status) -> Vec<&BatteryId> {
+    let mut ids = status
+        .batteries
+        .iter()
+        .map(|battery| &battery.id)
+        .collect::<Vec<_>>();
+    ids.sort();
+    ids
+}
+
+impl ChangedFields {
+    /// Fields that changed from `previous` to `current`.
+    ///
+    /// Readings that fluctuate on every query, like energy rate or voltage, are ignored.
+    pub fn between(previous: &Status, current: &Status) -> Self {
+        let mut changed = ChangedFields::empty();
+        changed.set(
+            ChangedFields::POWER_STATE,
+            previous.power_state != current.power_state,
+        );
+        changed.set(
+            ChangedFields::PERCENTAGE,
+            previous.estimated_energy_percentage != current.estimated_energy_percentage,
+        );
+        changed.set(
+            ChangedFields::TIME_REMAINING,
+            time_remaining_changed(
+                previous.estimated_time_remaining.as_ref(),
+                current.estimated_time_remaining.as_ref(),
+            ) || time_remaining_changed(
+                computed_time_remaining(previous),
+                computed_time_remaining(current),
+            ),
+        );
+        changed.set(
+            ChangedFields::SAVER_MODE,
+            previous.power_saving_mode != current.power_saving_mode,
+        );
+        changed.set(
+            ChangedFields::BATTERY_SET,
+            battery_ids(previous) != battery_ids(current),
+        );
+        changed.set(
+            ChangedFields::BATTERY_STATE,
+            current.batteries.iter().any(|battery| {
+                previous
+                    .batteries
+                    .iter()
+                    .find(|previous| previous.id == battery.id)
+                    .is_some_and(|previous| previous.state != battery.state)
+            }),
+        );
+        changed.set(
+            ChangedFields::POWER_SOURCES,
+            previous.power_sources.len() != current.power_sources.len()
+                || previous
+                    .power_sources
+                    .iter()
+                    .zip(&current.power_sources)
+                    .any(|(a, b)| a.name != b.name || a.online != b.online),
+        );
+        changed
+    }
+}
+
+/// A change of the power status, with the status before and after it.
+#[derive(Debug, Clone)]
+pub struct StatusChange {
+    pub previous: Status,
+    pub current: Status,
+    pub changed: ChangedFields,
+    /// Increases by one with every change delivered by the same registration.
+    pub sequence: u64,
+    pub timestamp: SystemTime,
+}
+
+impl StatusChange {
+    /// Whether the system switched from AC to battery power.
+    pub fn unplugged(&self) -> bool {
+        self.previous.power_state == PowerState::AC
+            && self.current.power_state == PowerState::Battery
+    }
+
+    /// Whether the system switched from battery to AC power.
+    pub fn plugged_in(&self) -> bool {
+        self.previous.power_state == PowerState::Battery
+            && self.current.power_state == PowerState::AC
+    }
+}
+
+/// Turns successive statuses into [`StatusChange`]s.
+#[derive(Debug, Default)]
+pub struct ChangeTracker {
+    previous: Status,
+    sequence: u64,
+}
+
+impl ChangeTracker {
+    /// Tracks changes starting from the `initial` status.
+    pub fn new(initial: Status) -> Self {
+        ChangeTracker {
+            previous: initial,
+            sequence: 0,
+        }
+    }
+
+    /// The last reported status, or the initial one.
+    pub fn current(&self) -> &Status {
+        &self.previous
+    }
+
+    /// Returns the change from the last reported status if any field changed.
+    ///
+    /// Statuses without a change are not recorded, so that slow drifts are reported once they
+    /// add up.
+    pub fn update(&mut self, current: Status) -> Option<StatusChange> {
+        let changed = ChangedFields::between(&self.previous, &current);
+        if changed.is_empty() {
+            return None;
+        }
+        let previous = std::mem::replace(&mut self.previous, current);
+        self.sequence += 1;
+        Some(StatusChange {
+            previous,
+            current: self.previous.clone(),
+            changed,
+            sequence: self.sequence,
+            timestamp: SystemTime::now(),
+        })
+    }
+}
+
+/// Wraps `cb` into a power state change callback that only reports actual changes.
+fn change_callback<F>(cb: F) -> impl Fn(Result<Status, Error>) + Send + Sync + 'static
+where
+    F: Fn(Result<StatusChange, Error>) + Send + Sync + 'static,
+{
+    // Changes are compared against the status at registration time.
+    let tracker = Mutex::new(ChangeTracker::new(
+        crate::get_current_power_state().unwrap_or_default(),
+    ));
+    move |status| match status {
+        Ok(status) => {
+            let change = tracker
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .update(status);
+            if let Some(change) = change {
+                cb(Ok(change));
+            }
+        }
+        Err(e) => cb(Err(e)),
+    }
+}
+
+/// Like [`crate::register_power_state_change_callback`], but delivers a [`StatusChange`] and
+/// skips notifications in which nothing changed.
+#[cfg(not(target_os = "macos"))]
+pub fn register_status_change_callback<F>(cb: F) -> Result<Guard, Error>
+where
+    F: Fn(Result<StatusChange, Error>) + Send + Sync + 'static,
+{
+    crate::register_power_state_change_callback(change_callback(cb))
+}
+
+/// Like [`crate::register_power_state_change_callback`], but delivers a [`StatusChange`] and
+/// skips notifications in which nothing changed.
+#[cfg(target_os = "macos")]
+pub fn register_status_change_callback<F>(
+    mtm: objc2::MainThreadMarker,
+    cb: F,
+) -> Result<Guard, Error>
+where
+    F: Fn(Result<StatusChange, Error>) + Send + Sync + 'static,
+{
+    crate::register_power_state_change_callback(mtm, change_callback(cb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BatteryInfo, BatteryState, test_util::status};
+
+    #[test]
+    fn test_changed_fields() {
+        let mut previous = status(PowerState::AC, 50);
+        previous.estimated_time_remaining =
+            Some(EstimatedTimeRemaining::Charging(Duration::from_secs(600)));
+
+        let mut current = previous.clone();
+        current.estimated_time_remaining =
+            Some(EstimatedTimeRemaining::Charging(Duration::from_secs(630)));
+        assert!(ChangedFields::between(&previous, &current).is_empty());
+
+        current.computed_time_remaining = Some(crate::TimeEstimate {
+            time_remaining: EstimatedTimeRemaining::Charging(Duration::from_secs(900)),
+            confidence: 0.5,
+        });
+        assert_eq!(
+            ChangedFields::between(&previous, &current),
+            ChangedFields::TIME_REMAINING
+        );
+        current.computed_time_remaining = None;
+
+        current.estimated_time_remaining = Some(EstimatedTimeRemaining::Discharging(
+            Duration::from_secs(630),
+        ));
+        assert_eq!(
+            ChangedFields::between(&previous, &current),
+            ChangedFields::TIME_REMAINING
+        );
+
+        current.power_state = PowerState::Battery;
+        current.estimated_energy_percentage = Some(49);
+        current.power_saving_mode = true;
+        current.batteries.push(Default::default());
+        assert_eq!(
+            ChangedFields::between(&previous, &current),
+            ChangedFields::POWER_STATE
+                | ChangedFields::PERCENTAGE
+                | ChangedFields::TIME_REMAINING
+                | ChangedFields::SAVER_MODE
+                | ChangedFields::BATTERY_SET
+        );
+    }
+
+    #[test]
+    fn test_battery_changes_by_id() {
+        let battery = |id: &str, state| BatteryInfo {
+            id: BatteryId::new(id),
+            state,
+            ..Default::default()
+        };
+        let mut previous = status(PowerState::AC, 50);
+        previous.batteries = vec![
+            battery("BAT0", BatteryState::Charging),
+            battery("BAT1", BatteryState::Full),
+        ];
+
+        // Reordering is not a change.
+        let mut current = previous.clone();
+        current.batteries.reverse();
+        assert!(ChangedFields::between(&previous, &current).is_empty());
+
+        current.batteries[1].state = BatteryState::NotCharging;
+        assert_eq!(
+            ChangedFields::between(&previous, &current),
+            ChangedFields::BATTERY_STATE
+        );
+
+        current.batteries[0] = battery("BAT2", BatteryState::Full);
+        assert_eq!(
+            ChangedFields::between(&previous, &current),
+            ChangedFields::BATTERY_SET | ChangedFields::BATTERY_STATE
+        );
+    }
+
+    #[test]
+    fn test_change_tracker() {
+        let mut tracker = ChangeTracker::new(status(PowerState::AC, 80));
+        assert!(tracker.update(status(PowerState::AC, 80)).is_none());
+
+        let change = tracker.update(status(PowerState::Battery, 80)).unwrap();
+        assert_eq!(change.changed, ChangedFields::POWER_STATE);
+        assert_eq!(change.sequence, 1);
+        assert!(change.unplugged());
+        assert!(!change.plugged_in());
+        assert_eq!(change.previous.power_state, PowerState::AC);
+        assert_eq!(change.current.power_state, PowerState::Battery);
+
+        let change = tracker.update(status(PowerState::Battery, 79)).unwrap();
+        assert_eq!(change.changed, ChangedFields::PERCENTAGE);
+        assert_eq!(change.sequence, 2);
+        assert!(!change.unplugged());
+        assert_eq!(tracker.current().estimated_energy_percentage, Some(79));
+    }
+
+    #[test]
+    fn test_slow_drift() {
+        let discharging = |secs| Status {
+            estimated_time_remaining: Some(EstimatedTimeRemaining::Discharging(
+                Duration::from_secs(secs),
+            )),
+            ..status(PowerState::Battery, 50)
+        };
+        let mut tracker = ChangeTracker::new(discharging(3600));
+        // Each step is below the tolerance, but together they are not.
+        assert!(tracker.update(discharging(3570)).is_none());
+        let change = tracker.update(discharging(3540)).unwrap();
+        assert_eq!(change.changed, ChangedFields::TIME_REMAINING);
+        assert_eq!(
+            change.previous.estimated_time_remaining,
+            Some(EstimatedTimeRemaining::Discharging(Duration::from_secs(
+                3600
+            )))
+        );
+        assert!(tracker.update(discharging(3510)).is_none());
+    }
+}
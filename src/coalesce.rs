@@ -0,0 +1,194 @@
+use std::{
+    panic,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{Error, OnPowerStateChange, Status};
+
+/// How bursts of notifications are merged into a single callback.
+///
+/// A single transition, like plugging in an adapter, often produces several OS notifications
+/// in quick succession. Statuses are held back until no new one arrived for `quiet_window`,
+/// and only the latest is delivered. A steady stream of notifications is still delivered at
+/// least every `max_latency`.
+///
+/// Coalescing is off unless set in [`crate::WatchOptions::coalesce`], as the statuses are then
+/// delivered from a separate thread instead of the thread of the backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoalesceOptions {
+    pub quiet_window: Duration,
+    pub max_latency: Duration,
+}
+
+impl Default for CoalesceOptions {
+    fn default() -> Self {
+        CoalesceOptions {
+            quiet_window: Duration::from_millis(100),
+            max_latency: Duration::from_millis(500),
+        }
+    }
+}
+
+enum Message {
+    Status(Result<Status, Error>),
+    Stop,
+}
+
+/// The thread delivering coalesced statuses, stopped when dropped.
+pub(crate) struct Coalescer {
+    tx: mpsc::Sender<Message>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Coalescer {
+    fn drop(&mut self) {
+        let _ = self.tx.send(Message::Stop);
+        if let Some(thread) = self.thread.take() {
+            // The guard may be dropped from inside the callback.
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Wraps `cb` into a callback for a backend, which delivers coalesced statuses from a
+/// separate thread when `options` are set.
+///
+/// The returned [`Coalescer`] must be kept alive by the guard of the registration.
+pub(crate) fn coalesce<F>(
+    options: Option<CoalesceOptions>,
+    cb: F,
+) -> Result<(OnPowerStateChange, Option<Coalescer>), Error>
+where
+    F: Fn(Result<Status, Error>) + Send + Sync + 'static,
+{
+    let Some(options) = options else {
+        return Ok((Box::new(cb), None));
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let thread = thread::Builder::new()
+        .name("powerstate-coalesce".to_string())
+        .spawn(move || run_coalescer(options, rx, cb))
+        .map_err(Error::CallbackThreadSpawnFailed)?;
+
+    let coalescer = Coalescer {
+        tx: tx.clone(),
+        thread: Some(thread),
+    };
+    let callback: OnPowerStateChange = Box::new(move |status| {
+        let _ = tx.send(Message::Status(status));
+    });
+    Ok((callback, Some(coalescer)))
+}
+
+fn run_coalescer<F>(options: CoalesceOptions, rx: mpsc::Receiver<Message>, cb: F)
+where
+    F: Fn(Result<Status, Error>),
+{
+    let deliver = |status| {
+        let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| cb(status)));
+    };
+
+    // The latest held back status, with the arrival times of the first and the latest status
+    // of the burst.
+    let mut pending: Option<(Status, Instant, Instant)> = None;
+    loop {
+        let message = match &pending {
+            None => rx.recv().ok(),
+            Some((_, first, last)) => {
+                let deadline = (*last + options.quiet_window).min(*first + options.max_latency);
+                match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(message) => Some(message),
+                    Err(RecvTimeoutError::Timeout) => {
+                        if let Some((status, _, _)) = pending.take() {
+                            deliver(Ok(status));
+                        }
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => None,
+                }
+            }
+        };
+
+        match message {
+            Some(Message::Status(Ok(status))) => {
+                let now = Instant::now();
+                let first = pending.map_or(now, |(_, first, _)| first);
+                pending = Some((status, first, now));
+            }
+            // Errors are not merged, but must not overtake the statuses before them.
+            Some(Message::Status(Err(e))) => {
+                if let Some((status, _, _)) = pending.take() {
+                    deliver(Ok(status));
+                }
+                deliver(Err(e));
+            }
+            Some(Message::Stop) | None => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{percentage, query_failed};
+
+    type Percentages = mpsc::Receiver<Result<Option<u8>, Error>>;
+
+    fn start(
+        options: Option<CoalesceOptions>,
+    ) -> (OnPowerStateChange, Option<Coalescer>, Percentages) {
+        let (tx, rx) = mpsc::channel();
+        let (callback, coalescer) = coalesce(options, move |status| {
+            let _ = tx.send(status.map(|status| status.estimated_energy_percentage));
+        })
+        .unwrap();
+        (callback, coalescer, rx)
+    }
+
+    #[test]
+    fn test_burst_is_delivered_once() {
+        let (callback, _coalescer, rx) = start(Some(CoalesceOptions {
+            quiet_window: Duration::from_millis(50),
+            max_latency: Duration::from_secs(5),
+        }));
+        (1..=5).for_each(|value| callback(percentage(value)));
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(rx.recv_timeout(timeout).unwrap().unwrap(), Some(5));
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        callback(percentage(6));
+        callback(Err(query_failed()));
+        assert_eq!(rx.recv_timeout(timeout).unwrap().unwrap(), Some(6));
+        assert!(rx.recv_timeout(timeout).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_max_latency() {
+        let (callback, _coalescer, rx) = start(Some(CoalesceOptions {
+            quiet_window: Duration::from_millis(50),
+            max_latency: Duration::from_millis(100),
+        }));
+        // Never quiet for long enough, but still delivered.
+        for value in 0..40 {
+            callback(percentage(value));
+            thread::sleep(Duration::from_millis(10));
+        }
+        let delivered = rx.try_iter().count();
+        assert!(delivered >= 2, "delivered {delivered} times");
+    }
+
+    #[test]
+    fn test_disabled() {
+        let (callback, coalescer, rx) = start(None);
+        assert!(coalescer.is_none());
+        callback(percentage(1));
+        callback(percentage(2));
+        assert_eq!(rx.try_iter().count(), 2);
+    }
+}
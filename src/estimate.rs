@@ -0,0 +1,335 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    BatteryInfo, BatteryState, Energy, Error, EstimatedTimeRemaining, PollingGuard, Power, Status,
+    get_current_power_state, polling::spawn_poller,
+};
+
+/// Time constant used by [`TimeEstimator::default`].
+pub const DEFAULT_TIME_CONSTANT: Duration = Duration::from_secs(120);
+
+/// Number of samples after which an estimate of a steady rate gets full confidence.
+const WARMUP_SAMPLES: u32 = 5;
+
+/// A time remaining computed by [`TimeEstimator`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeEstimate {
+    pub time_remaining: EstimatedTimeRemaining,
+    /// How far the estimate can be trusted, in range [0, 1].
+    ///
+    /// Low right after the batteries started charging or discharging, and while the rate varies.
+    pub confidence: f32,
+}
+
+/// Computes a smoothed time remaining from successive battery readings.
+///
+/// The OS estimates are often missing or jumping right after plugging or unplugging. This keeps
+/// an exponentially weighted moving average of the net energy rate of the batteries instead,
+/// using the energy they gained or lost when the OS reports no rate.
+#[derive(Debug, Clone)]
+pub struct TimeEstimator {
+    time_constant: Duration,
+    last: Option<(Instant, Option<Energy>)>,
+    /// Net rate in watts, positive when charging.
+    rate: Option<f32>,
+    variance: f32,
+    samples: u32,
+}
+
+impl Default for TimeEstimator {
+    fn default() -> Self {
+        Self::new(DEFAULT_TIME_CONSTANT)
+    }
+}
+
+impl TimeEstimator {
+    /// Creates an estimator weighting readings older than `time_constant` less and less.
+    pub fn new(time_constant: Duration) -> Self {
+        TimeEstimator {
+            time_constant,
+            last: None,
+            rate: None,
+            variance: 0.0,
+            samples: 0,
+        }
+    }
+
+    /// The smoothed net energy rate of the batteries, positive when charging.
+    pub fn rate(&self) -> Option<f32> {
+        self.rate
+    }
+
+    /// Forgets all readings, e.g. after the system woke up.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.time_constant);
+    }
+
+    /// Adds a reading of `batteries` taken now and returns the updated estimate.
+    pub fn update(&mut self, batteries: &[BatteryInfo]) -> Option<TimeEstimate> {
+        self.update_at(batteries, Instant::now())
+    }
+
+    /// Adds a reading of `batteries` taken at `at` and returns the updated estimate.
+    pub fn update_at(&mut self, batteries: &[BatteryInfo], at: Instant) -> Option<TimeEstimate> {
+        let energy = total_energy(batteries);
+        let last = self.last.replace((at, energy));
+        let in_use = batteries.iter().any(|battery| {
+            matches!(
+                battery.state,
+                BatteryState::Charging | BatteryState::Discharging
+            )
+        });
+        if !in_use {
+            self.rate = None;
+            self.samples = 0;
+            return None;
+        }
+
+        let elapsed = last.map(|(last_at, _)| at.saturating_duration_since(last_at));
+        let rate = reported_rate(batteries).or_else(|| {
+            let (_, last_energy) = last?;
+            let delta = energy?.joules() - last_energy?.joules();
+            let elapsed = elapsed?.as_secs_f32();
+            // Energy is often reported in coarse steps, so an unchanged reading says little.
+            (elapsed > 0.0 && delta != 0.0).then(|| delta / elapsed)
+        });
+        if let Some(rate) = rate {
+            self.add_rate(rate, elapsed.unwrap_or_default());
+        }
+        self.estimate(batteries)
+    }
+
+    /// Fills [`Status::computed_time_remaining`] from a reading of its batteries taken now.
+    pub fn update_status(&mut self, status: &mut Status) {
+        status.computed_time_remaining = self.update(&status.batteries);
+    }
+
+    fn add_rate(&mut self, rate: f32, elapsed: Duration) {
+        match self.rate {
+            // A repeated reading adds no information.
+            Some(average) if elapsed.is_zero() && average.signum() == rate.signum() => {}
+            // Switching between charging and discharging makes older readings meaningless.
+            Some(average) if average.signum() == rate.signum() => {
+                let alpha = 1.0 - (-elapsed.as_secs_f32() / self.time_constant.as_secs_f32()).exp();
+                let diff = rate - average;
+                self.rate = Some(average + alpha * diff);
+                self.variance = (1.0 - alpha) * (self.variance + alpha * diff * diff);
+                self.samples = self.samples.saturating_add(1);
+            }
+            _ => {
+                self.rate = Some(rate);
+                self.variance = 0.0;
+                self.samples = 1;
+            }
+        }
+    }
+
+    fn confidence(&self, rate: f32) -> f32 {
+        let warmup = self.samples.min(WARMUP_SAMPLES) as f32 / WARMUP_SAMPLES as f32;
+        let variation = self.variance.sqrt() / rate.abs();
+        (warmup / (1.0 + variation)).clamp(0.0, 1.0)
+    }
+
+    fn estimate(&self, batteries: &[BatteryInfo]) -> Option<TimeEstimate> {
+        let rate = self.rate?;
+        let energy = total_energy(batteries)?;
+        let time_remaining = if rate < 0.0 {
+            EstimatedTimeRemaining::Discharging(energy.time_at(Power::from_watts(-rate))?)
+        } else {
+            let energy_full: Energy = batteries
+                .iter()
+                .filter_map(|battery| battery.energy_full)
+                .sum();
+            EstimatedTimeRemaining::Charging(
+                (energy_full - energy).time_at(Power::from_watts(rate))?,
+            )
+        };
+        Some(TimeEstimate {
+            time_remaining,
+            confidence: self.confidence(rate),
+        })
+    }
+}
+
+fn total_energy(batteries: &[BatteryInfo]) -> Option<Energy> {
+    let mut energies = batteries
+        .iter()
+        .filter_map(|battery| battery.energy)
+        .peekable();
+    energies.peek()?;
+    Some(energies.sum())
+}
+
+/// Net rate reported by the OS, ignoring the zero rates some report while still measuring.
+fn reported_rate(batteries: &[BatteryInfo]) -> Option<f32> {
+    let rates = batteries
+        .iter()
+        .filter_map(|battery| {
+            let rate = battery.energy_rate?.watts();
+            match battery.state {
+                BatteryState::Charging if rate > 0.0 => Some(rate),
+                BatteryState::Discharging if rate > 0.0 => Some(-rate),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    (!rates.is_empty()).then(|| rates.iter().sum())
+}
+
+/// Polls [`get_current_power_state`] every `interval`, feeding each reading to `estimator`, and
+/// invokes the callback when the status changed.
+///
+/// Unlike [`crate::register_polling_callback`], the statuses carry
+/// [`Status::computed_time_remaining`]. A short interval makes the estimate settle faster.
+pub fn register_time_estimate_callback<F>(
+    interval: Duration,
+    mut estimator: TimeEstimator,
+    cb: F,
+) -> Result<PollingGuard, Error>
+where
+    F: Fn(Result<Status, Error>) + Send + Sync + 'static,
+{
+    let source = move || {
+        let mut status = get_current_power_state()?;
+        estimator.update_status(&mut status);
+        Ok(status)
+    };
+    spawn_poller(interval, source, cb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battery(state: BatteryState, energy: f32, rate: Option<f32>) -> BatteryInfo {
+        BatteryInfo {
+            state,
+            energy: Some(Energy::from_watt_hours(energy)),
+            energy_full: Some(Energy::from_watt_hours(60.0)),
+            energy_rate: rate.map(Power::from_watts),
+            ..Default::default()
+        }
+    }
+
+    /// Feeds one discharging reading per minute with the given rates, starting at 30 Wh.
+    fn discharge(estimator: &mut TimeEstimator, rates: &[f32]) -> Option<TimeEstimate> {
+        let start = Instant::now();
+        let mut energy = 30.0;
+        let mut estimate = None;
+        for (minute, rate) in rates.iter().enumerate() {
+            let at = start + Duration::from_secs(minute as u64 * 60);
+            estimate = estimator.update_at(
+                &[battery(BatteryState::Discharging, energy, Some(*rate))],
+                at,
+            );
+            energy -= rate / 60.0;
+        }
+        estimate
+    }
+
+    fn discharging_hours(estimate: &TimeEstimate) -> f32 {
+        match estimate.time_remaining {
+            EstimatedTimeRemaining::Discharging(time) => time.as_secs_f32() / 3600.0,
+            EstimatedTimeRemaining::Charging(_) => panic!("expected discharging"),
+        }
+    }
+
+    #[test]
+    fn test_steady_rate() {
+        let mut estimator = TimeEstimator::default();
+        let first = discharge(&mut estimator, &[10.0]).unwrap();
+        assert!((discharging_hours(&first) - 3.0).abs() < 0.01);
+        assert!(first.confidence < 0.5);
+
+        estimator.reset();
+        let settled = discharge(&mut estimator, &[10.0; 10]).unwrap();
+        assert!((estimator.rate().unwrap() + 10.0).abs() < 0.01);
+        assert!((discharging_hours(&settled) - 28.5 / 10.0).abs() < 0.01);
+        assert_eq!(settled.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_repeated_reading() {
+        let start = Instant::now();
+        let mut estimator = TimeEstimator::default();
+        let reading = [battery(BatteryState::Discharging, 30.0, Some(10.0))];
+        let first = estimator.update_at(&reading, start).unwrap();
+        for _ in 0..5 {
+            let repeated = estimator.update_at(&reading, start).unwrap();
+            assert_eq!(repeated, first);
+        }
+    }
+
+    #[test]
+    fn test_smoothing() {
+        let mut steady = TimeEstimator::default();
+        let steady = discharge(&mut steady, &[10.0; 10]).unwrap();
+        let mut noisy = TimeEstimator::default();
+        let noisy = discharge(
+            &mut noisy,
+            &[5.0, 15.0, 5.0, 15.0, 5.0, 15.0, 5.0, 15.0, 5.0, 30.0],
+        )
+        .unwrap();
+        // The OS would report under an hour from the last rate alone.
+        assert!(discharging_hours(&noisy) > 1.5);
+        assert!(noisy.confidence < steady.confidence);
+    }
+
+    #[test]
+    fn test_rate_from_energy() {
+        // Rates reported as zero or missing while the OS is still measuring.
+        let start = Instant::now();
+        let mut estimator = TimeEstimator::default();
+        assert_eq!(
+            estimator.update_at(
+                &[battery(BatteryState::Discharging, 30.0, Some(0.0))],
+                start
+            ),
+            None
+        );
+        let estimate = estimator
+            .update_at(
+                &[battery(BatteryState::Discharging, 29.5, None)],
+                start + Duration::from_secs(180),
+            )
+            .unwrap();
+        assert!((estimator.rate().unwrap() + 10.0).abs() < 0.01);
+        assert!((discharging_hours(&estimate) - 2.95).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_state_changes() {
+        let start = Instant::now();
+        let mut estimator = TimeEstimator::default();
+        discharge(&mut estimator, &[10.0; 5]);
+
+        // Plugging in discards the discharging readings.
+        let estimate = estimator
+            .update_at(
+                &[battery(BatteryState::Charging, 30.0, Some(20.0))],
+                start + Duration::from_secs(600),
+            )
+            .unwrap();
+        assert_eq!(
+            estimate.time_remaining,
+            EstimatedTimeRemaining::Charging(Duration::from_secs(90 * 60))
+        );
+        assert!(estimate.confidence < 0.5);
+
+        let full = [battery(BatteryState::Full, 60.0, Some(0.0))];
+        assert_eq!(
+            estimator.update_at(&full, start + Duration::from_secs(660)),
+            None
+        );
+        assert_eq!(estimator.rate(), None);
+
+        let mut status = Status {
+            batteries: vec![battery(BatteryState::Discharging, 30.0, Some(10.0))],
+            ..Default::default()
+        };
+        estimator.update_status(&mut status);
+        assert!(status.computed_time_remaining.is_some());
+        assert_eq!(status.estimated_time_remaining, None);
+    }
+}
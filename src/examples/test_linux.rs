@@ -0,0 +1,18 @@
+#[cfg(target_os = "linux")]
+use powerstate::register_power_state_change_callback;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    simple_logging::log_to_stderr(log::LevelFilter::Trace);
+    let _guard = register_power_state_change_callback(|status| {
+        println!("{status:#?}");
+    })
+    .unwrap();
+
+    std::thread::sleep(std::time::Duration::from_secs(10));
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("This is a linux example, please run it on linux");
+}
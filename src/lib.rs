@@ -1,17 +1,84 @@
 use std::time::Duration;
+mod aggregate;
+mod backend;
 mod batteries;
+mod change;
+mod coalesce;
+mod estimate;
+mod monitor;
 mod os_impl;
+mod polling;
+mod power_sources;
+#[cfg(feature = "serde")]
+mod serde_helpers;
+#[cfg(feature = "async")]
+mod stream;
+mod subscription;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "testing")]
+mod testing;
+mod threshold;
+mod units;
+mod watch;
+
+pub use aggregate::{combined_energy_percentage, combined_time_remaining};
+
+pub use backend::{
+    BACKEND_ENV_VAR, BackendGuard, Capabilities, PowerBackend, backend, backend_names,
+    default_backend,
+};
+
+pub use batteries::{
+    BatteryId, BatteryInfo, BatteryState, BatteryTechnology, PeripheralBattery, PeripheralKind,
+    get_battery,
+};
+
+pub use change::{ChangeTracker, ChangedFields, StatusChange, register_status_change_callback};
+
+pub use coalesce::CoalesceOptions;
+
+pub use estimate::{
+    DEFAULT_TIME_CONSTANT, TimeEstimate, TimeEstimator, register_time_estimate_callback,
+};
 
-pub use batteries::{BatteryInfo, BatteryState, BatteryTechnology};
+pub use monitor::{OnMonitorChange, PowerMonitor, PowerMonitorBuilder, SubscriberId};
 
 pub use os_impl::*;
 
+pub use polling::{DEFAULT_POLLING_INTERVAL, PollingGuard, register_polling_callback};
+
+pub use power_sources::{PowerSource, PowerSourceKind};
+
+#[cfg(feature = "async")]
+pub use stream::{PowerStateStream, power_state_stream};
+
+pub use subscription::{Events, OverflowPolicy, SubscribeOptions, Subscription, subscribe};
+
+#[cfg(feature = "testing")]
+pub use testing::{MockBackend, Timeline};
+
+pub use threshold::{
+    DEFAULT_HYSTERESIS, Threshold, ThresholdDirection, ThresholdEvent, ThresholdWatcher,
+    register_threshold_callback,
+};
+
+pub use units::{Energy, Power, Temperature, Voltage};
+
+pub use watch::WatchOptions;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("failed to spawn callback thread: {0}")]
     CallbackThreadSpawnFailed(#[source] std::io::Error),
     #[error("failed to receive callback registration result: {0}")]
     CallbackRegistrationChannelClosed(#[from] oneshot::RecvError),
+    #[error("sleep events are not supported on this platform")]
+    SleepEventsNotSupported,
+    #[error("peripheral batteries are not supported on this platform")]
+    PeripheralBatteriesNotSupported,
+    #[error("unknown power backend: {0}")]
+    UnknownBackend(String),
     #[cfg(target_os = "windows")]
     #[error(transparent)]
     Windows(#[from] windows::core::Error),
@@ -19,32 +86,90 @@ pub enum Error {
     #[error(transparent)]
     Macos(#[from] MacosError),
     #[cfg(target_os = "linux")]
-    #[error("Linux is not supported")]
-    Linux,
+    #[error(transparent)]
+    Linux(#[from] LinuxError),
 }
 
-#[derive(Debug, Clone)]
+/// Serialized as e.g. `{"charging": 5400.0}`, in seconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum EstimatedTimeRemaining {
-    Charging(Duration),
-    Discharging(Duration),
+    Charging(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::duration_secs"))]
+        Duration,
+    ),
+    Discharging(
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_helpers::duration_secs"))]
+        Duration,
+    ),
 }
 
+/// With the `serde` feature, missing fields deserialize to their default, so that statuses of
+/// older versions of this crate can still be read.
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct Status {
     pub power_state: PowerState,
     /// Estimated energy percentage, in range [0, 100].
     pub estimated_energy_percentage: Option<u8>,
+    /// Time remaining as reported by the OS, or computed from the current rates of the batteries
+    /// when the OS doesn't report one.
     pub estimated_time_remaining: Option<EstimatedTimeRemaining>,
+    /// Time remaining as reported by the OS, unmodified.
+    ///
+    /// Always `None` with sysfs, which has no system-wide estimate.
+    pub os_time_remaining: Option<EstimatedTimeRemaining>,
+    /// Time remaining smoothed over successive readings by a [`TimeEstimator`], see
+    /// [`PowerMonitorBuilder::time_estimator`] and [`register_time_estimate_callback`].
+    pub computed_time_remaining: Option<TimeEstimate>,
     pub batteries: Vec<BatteryInfo>,
+    /// External power sources, e.g. AC adapters and USB chargers.
+    ///
+    /// Only reported on Linux for now.
+    pub power_sources: Vec<PowerSource>,
     /// Whether the system is in power saving mode.
     ///
     /// In macos, this also called `Low Power Mode`
     pub power_saving_mode: bool,
 }
 
-type OnPowerStateChange = Box<dyn Fn(Result<Status, Error>) + Send + Sync>;
+/// Callback receiving the new status whenever the power state changes.
+pub type OnPowerStateChange = Box<dyn Fn(Result<Status, Error>) + Send + Sync>;
+
+/// A system sleep transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepEvent {
+    /// The system is about to go to sleep.
+    WillSleep,
+    /// The system has woken up.
+    DidWake,
+}
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone)]
+pub struct SleepOptions {
+    /// Whether to delay sleep until the callback for [`SleepEvent::WillSleep`] returns.
+    ///
+    /// The OS still puts a time limit on the delay, e.g. `InhibitDelayMaxSec` of logind.
+    pub delay_sleep: bool,
+}
+
+#[cfg_attr(not(all(target_os = "linux", feature = "dbus")), allow(dead_code))]
+type OnSleepEvent = Box<dyn Fn(SleepEvent) + Send + Sync>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum PowerState {
     Battery,
     AC,
@@ -59,10 +184,6 @@ mod tests {
     #[test]
     fn test_get_current_power_state() {
         let status = get_current_power_state();
-        #[cfg(target_os = "linux")]
-        assert!(matches!(status, Err(Error::Linux)));
-
-        #[cfg(not(target_os = "linux"))]
         println!("{:#?}", status.unwrap());
     }
 }
@@ -0,0 +1,395 @@
+use std::{
+    panic,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{
+    BackendGuard, Error, OnPowerStateChange, PowerBackend, Status, TimeEstimator, WatchOptions,
+};
+
+/// Callback of a [`PowerMonitor`] subscriber.
+///
+/// The result is shared by all subscribers, so it is passed by reference.
+pub type OnMonitorChange = Box<dyn Fn(&Result<Status, Error>) + Send + Sync>;
+
+/// Identifies a subscriber of a [`PowerMonitor`], to unsubscribe it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriberId(u64);
+
+#[derive(Default)]
+struct Hub {
+    status: RwLock<Option<Status>>,
+    subscribers: Mutex<Vec<(SubscriberId, Arc<OnMonitorChange>)>>,
+    next_id: AtomicU64,
+}
+
+impl Hub {
+    fn subscribe(&self, cb: OnMonitorChange) -> SubscriberId {
+        let id = SubscriberId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((id, Arc::new(cb)));
+        id
+    }
+
+    fn publish(&self, status: Result<Status, Error>) {
+        if let Ok(status) = &status {
+            *self.status.write().unwrap_or_else(|e| e.into_inner()) = Some(status.clone());
+        }
+        // Subscribers may subscribe or unsubscribe from inside their callback.
+        let subscribers = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(_, cb)| cb.clone())
+            .collect::<Vec<_>>();
+        for cb in subscribers {
+            let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| cb(&status)));
+        }
+    }
+}
+
+/// Shares a single OS registration between any number of subscribers.
+///
+/// The monitor keeps the latest status cached, so it can be queried without going to the OS.
+/// Wrap it in an [`Arc`] to share it across threads; the registration ends when it is dropped.
+///
+/// [`PowerMonitor::builder`] works the same on every platform. On macOS the notifications are
+/// delivered on a dedicated run loop thread, so no main thread run loop is needed.
+pub struct PowerMonitor<G = BackendGuard> {
+    // Dropped first, so that the callback stops before the hub goes away.
+    _guard: G,
+    hub: Arc<Hub>,
+}
+
+impl<G> PowerMonitor<G> {
+    /// Fans out the changes delivered to the callback passed to `register`, which returns the
+    /// guard of the registration, e.g. [`crate::register_power_state_change_callback`].
+    ///
+    /// The cache starts with `initial`.
+    pub fn with_registration<R>(initial: Option<Status>, register: R) -> Result<Self, Error>
+    where
+        R: FnOnce(OnPowerStateChange) -> Result<G, Error>,
+    {
+        Self::with_hub(
+            Hub {
+                status: RwLock::new(initial),
+                ..Default::default()
+            },
+            register,
+        )
+    }
+
+    fn with_hub<R>(hub: Hub, register: R) -> Result<Self, Error>
+    where
+        R: FnOnce(OnPowerStateChange) -> Result<G, Error>,
+    {
+        let hub = Arc::new(hub);
+        let guard = register(Box::new({
+            let hub = hub.clone();
+            move |status| hub.publish(status)
+        }))?;
+        Ok(PowerMonitor { _guard: guard, hub })
+    }
+
+    /// The latest status, if any was received yet.
+    pub fn status(&self) -> Option<Status> {
+        self.hub
+            .status
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Invokes the callback on every change until unsubscribed.
+    pub fn subscribe<F>(&self, cb: F) -> SubscriberId
+    where
+        F: Fn(&Result<Status, Error>) + Send + Sync + 'static,
+    {
+        self.hub.subscribe(Box::new(cb))
+    }
+
+    /// Removes a subscriber, returning whether it was subscribed.
+    pub fn unsubscribe(&self, id: SubscriberId) -> bool {
+        let mut subscribers = self
+            .hub
+            .subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let len = subscribers.len();
+        subscribers.retain(|(subscriber, _)| *subscriber != id);
+        subscribers.len() != len
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.hub
+            .subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+impl PowerMonitor {
+    /// Configures a monitor registered with the OS.
+    pub fn builder() -> PowerMonitorBuilder {
+        PowerMonitorBuilder::default()
+    }
+
+    /// Registers for every kind of change and caches the current status.
+    pub fn start() -> Result<Self, Error> {
+        Self::builder().start()
+    }
+}
+
+/// Builds a [`PowerMonitor`], see [`PowerMonitor::builder`].
+#[derive(Default)]
+pub struct PowerMonitorBuilder {
+    options: WatchOptions,
+    backend: Option<Box<dyn PowerBackend>>,
+    subscribers: Vec<OnMonitorChange>,
+    estimator: Option<TimeEstimator>,
+}
+
+impl PowerMonitorBuilder {
+    /// Uses `backend` instead of [`crate::default_backend`].
+    pub fn backend<B: PowerBackend + 'static>(mut self, backend: B) -> Self {
+        self.backend = Some(Box::new(backend));
+        self
+    }
+
+    /// Only registers for the changes selected by `options`.
+    pub fn watch(mut self, options: WatchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Feeds every status to `estimator`, filling [`Status::computed_time_remaining`].
+    ///
+    /// The estimate only settles with regular readings, see
+    /// [`crate::register_time_estimate_callback`] for a polling alternative.
+    pub fn time_estimator(mut self, estimator: TimeEstimator) -> Self {
+        self.estimator = Some(estimator);
+        self
+    }
+
+    /// Subscribes the callback before registering, so it doesn't miss any change.
+    pub fn on_change<F>(mut self, cb: F) -> Self
+    where
+        F: Fn(&Result<Status, Error>) + Send + Sync + 'static,
+    {
+        self.subscribers.push(Box::new(cb));
+        self
+    }
+
+    /// Subscribes to the backend and caches the current status.
+    pub fn start(self) -> Result<PowerMonitor, Error> {
+        let backend = match self.backend {
+            Some(backend) => backend,
+            None => crate::default_backend()?,
+        };
+        let estimator = self.estimator.map(Mutex::new).map(Arc::new);
+        let initial = backend.status().ok().map(|status| match &estimator {
+            Some(estimator) => estimate(estimator, status),
+            None => status,
+        });
+        let hub = Hub {
+            status: RwLock::new(initial),
+            ..Default::default()
+        };
+        for cb in self.subscribers {
+            hub.subscribe(cb);
+        }
+        PowerMonitor::with_hub(hub, |cb| {
+            let cb: OnPowerStateChange = match estimator {
+                Some(estimator) => {
+                    Box::new(move |status| cb(status.map(|status| estimate(&estimator, status))))
+                }
+                None => cb,
+            };
+            backend.subscribe(self.options, cb)
+        })
+    }
+}
+
+fn estimate(estimator: &Mutex<TimeEstimator>, mut status: Status) -> Status {
+    estimator
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .update_status(&mut status);
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use super::*;
+    use crate::{
+        BatteryInfo, BatteryState, Energy, EstimatedTimeRemaining, Power, PowerState,
+        test_util::query_failed,
+    };
+
+    fn fake_monitor() -> (PowerMonitor<()>, Arc<Mutex<Option<OnPowerStateChange>>>) {
+        let callback = Arc::new(Mutex::new(None));
+        let monitor = PowerMonitor::with_registration(None, |cb| {
+            *callback.lock().unwrap() = Some(cb);
+            Ok(())
+        })
+        .unwrap();
+        (monitor, callback)
+    }
+
+    struct FakeBackend(Arc<Mutex<Option<OnPowerStateChange>>>);
+
+    impl PowerBackend for FakeBackend {
+        fn name(&self) -> &str {
+            "fake"
+        }
+
+        fn capabilities(&self) -> crate::Capabilities {
+            crate::Capabilities::NOTIFICATIONS
+        }
+
+        fn status(&self) -> Result<Status, Error> {
+            Ok(Status {
+                power_state: PowerState::AC,
+                ..Default::default()
+            })
+        }
+
+        fn subscribe(
+            &self,
+            _options: WatchOptions,
+            cb: OnPowerStateChange,
+        ) -> Result<BackendGuard, Error> {
+            *self.0.lock().unwrap() = Some(cb);
+            Ok(BackendGuard::new(()))
+        }
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let (monitor, callback) = fake_monitor();
+        let notify = |status| (callback.lock().unwrap().as_ref().unwrap())(status);
+        assert!(monitor.status().is_none());
+
+        let (tx, rx) = mpsc::channel();
+        let ids = (0..2)
+            .map(|subscriber| {
+                let tx = tx.clone();
+                monitor.subscribe(move |status| {
+                    let _ = tx.send((subscriber, status.as_ref().ok().map(|s| s.power_state)));
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(monitor.subscriber_count(), 2);
+
+        notify(Ok(Status {
+            power_state: PowerState::Battery,
+            ..Default::default()
+        }));
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            [
+                (0, Some(PowerState::Battery)),
+                (1, Some(PowerState::Battery))
+            ]
+        );
+        assert_eq!(monitor.status().unwrap().power_state, PowerState::Battery);
+
+        assert!(monitor.unsubscribe(ids[0]));
+        assert!(!monitor.unsubscribe(ids[0]));
+        notify(Err(query_failed()));
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [(1, None)]);
+        // Errors keep the last known status.
+        assert_eq!(monitor.status().unwrap().power_state, PowerState::Battery);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_start_shared() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let monitor = Arc::new(PowerMonitor::start().unwrap());
+        assert_send_sync(&monitor);
+        assert!(monitor.status().is_some());
+
+        let shared = monitor.clone();
+        std::thread::spawn(move || shared.subscribe(|_| {}))
+            .join()
+            .unwrap();
+        assert_eq!(monitor.subscriber_count(), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_builder() {
+        let monitor = PowerMonitor::builder()
+            .watch(WatchOptions {
+                saver_mode: false,
+                ..Default::default()
+            })
+            .on_change(|_| {})
+            .on_change(|_| {})
+            .start()
+            .unwrap();
+        assert_eq!(monitor.subscriber_count(), 2);
+        assert!(monitor.status().is_some());
+    }
+
+    #[test]
+    fn test_custom_backend() {
+        let callback = Arc::new(Mutex::new(None));
+        let (tx, rx) = mpsc::channel();
+        let monitor = PowerMonitor::builder()
+            .backend(FakeBackend(callback.clone()))
+            .on_change(move |status| {
+                let _ = tx.send(status.as_ref().ok().map(|s| s.power_state));
+            })
+            .start()
+            .unwrap();
+        assert_eq!(monitor.status().unwrap().power_state, PowerState::AC);
+
+        (callback.lock().unwrap().as_ref().unwrap())(Ok(Status {
+            power_state: PowerState::Battery,
+            ..Default::default()
+        }));
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            [Some(PowerState::Battery)]
+        );
+    }
+
+    #[test]
+    fn test_time_estimator() {
+        let callback = Arc::new(Mutex::new(None));
+        let monitor = PowerMonitor::builder()
+            .backend(FakeBackend(callback.clone()))
+            .time_estimator(TimeEstimator::default())
+            .start()
+            .unwrap();
+        assert!(monitor.status().unwrap().computed_time_remaining.is_none());
+
+        (callback.lock().unwrap().as_ref().unwrap())(Ok(Status {
+            power_state: PowerState::Battery,
+            batteries: vec![BatteryInfo {
+                state: BatteryState::Discharging,
+                energy: Some(Energy::from_watt_hours(30.0)),
+                energy_rate: Some(Power::from_watts(10.0)),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }));
+        let estimate = monitor.status().unwrap().computed_time_remaining.unwrap();
+        assert_eq!(
+            estimate.time_remaining,
+            EstimatedTimeRemaining::Discharging(Duration::from_secs(3 * 3600))
+        );
+    }
+}
@@ -10,6 +10,9 @@ mod linux;
 #[cfg(target_os = "linux")]
 pub use linux::*;
 
+#[cfg(target_os = "linux")]
+pub use linux::Error as LinuxError;
+
 #[cfg(target_os = "macos")]
 mod macos;
 
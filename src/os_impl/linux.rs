@@ -1,15 +1,446 @@
-use crate::Status;
+use std::{
+    io,
+    os::fd::AsRawFd,
+    panic,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
 
-pub struct Guard;
+use crate::{
+    BackendGuard, Capabilities, DEFAULT_POLLING_INTERVAL, OnPowerStateChange, PeripheralBattery,
+    PollingGuard, PowerBackend, SleepEvent, SleepOptions, Status, WatchOptions,
+    coalesce::{Coalescer, coalesce},
+    polling::spawn_poller,
+    watch,
+};
 
+mod acpi;
+#[cfg(feature = "dbus")]
+mod dbus;
+mod event_fd;
+#[cfg(feature = "dbus")]
+mod logind;
+#[cfg(feature = "dbus")]
+mod power_profiles;
+mod sysfs;
+#[cfg(all(test, feature = "dbus"))]
+mod test_bus;
+mod uevent;
+#[cfg(feature = "dbus")]
+mod upower;
+
+use event_fd::EventFd;
+use uevent::UeventSocket;
+
+pub use acpi::{
+    DEFAULT_ACPID_SOCKET_PATH, register_acpi_netlink_callback, register_acpid_callback,
+};
+#[cfg(feature = "dbus")]
+pub use logind::Logind;
+#[cfg(feature = "dbus")]
+pub use power_profiles::{PowerProfile, PowerProfiles, ProfileHold};
+pub use sysfs::{DEFAULT_POWER_SUPPLY_PATH, Sysfs};
+#[cfg(feature = "dbus")]
+pub use upower::{UPower, UPowerWarningLevel};
+
+const POWER_SUPPLY_SUBSYSTEM: &str = "power_supply";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to read power supply directory {0}")]
+    FailedToReadPowerSupplyDir(std::path::PathBuf, #[source] io::Error),
+    #[error("Failed to create event fd")]
+    FailedToCreateEventFd(#[source] io::Error),
+    #[error("Failed to open uevent netlink socket")]
+    FailedToOpenUeventSocket(#[source] io::Error),
+    #[error("Failed to receive uevent")]
+    FailedToReceiveUevent(#[source] io::Error),
+    #[error("Failed to connect to acpid socket {0}")]
+    FailedToConnectAcpid(std::path::PathBuf, #[source] io::Error),
+    #[error("Failed to open ACPI netlink socket")]
+    FailedToOpenAcpiNetlinkSocket(#[source] io::Error),
+    #[error("ACPI netlink family is not available")]
+    AcpiNetlinkFamilyNotFound,
+    #[error("Failed to receive ACPI event")]
+    FailedToReceiveAcpiEvent(#[source] io::Error),
+    #[cfg(feature = "dbus")]
+    #[error("D-Bus error")]
+    DBus(#[from] zbus::Error),
+    #[cfg(feature = "dbus")]
+    #[error("Unknown power profile: {0}")]
+    UnknownPowerProfile(String),
+}
+
+enum Stop {
+    EventFd(Arc<EventFd>),
+    #[cfg_attr(not(feature = "dbus"), allow(dead_code))]
+    Channel(Option<oneshot::Sender<()>>),
+}
+
+/// A background thread delivering notifications, stopped when dropped.
+struct Worker {
+    stop: Stop,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        match &mut self.stop {
+            Stop::EventFd(event_fd) => event_fd.notify(),
+            Stop::Channel(sender) => {
+                if let Some(sender) = sender.take() {
+                    let _ = sender.send(());
+                }
+            }
+        }
+        if let Some(thread) = self.thread.take() {
+            // The guard may be dropped from inside the callback.
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Stops the background threads of a registered callback when dropped.
+pub struct Guard {
+    _backend: Option<BackendGuard>,
+    _workers: Vec<Worker>,
+    _polling: Option<PollingGuard>,
+    // Dropped after the workers, which feed it.
+    _coalescer: Option<Coalescer>,
+}
+
+impl Guard {
+    fn with_coalescer(mut self, coalescer: Option<Coalescer>) -> Self {
+        self._coalescer = coalescer;
+        self
+    }
+}
+
+impl From<Vec<Worker>> for Guard {
+    fn from(workers: Vec<Worker>) -> Self {
+        Guard {
+            _backend: None,
+            _workers: workers,
+            _polling: None,
+            _coalescer: None,
+        }
+    }
+}
+
+impl From<BackendGuard> for Guard {
+    fn from(guard: BackendGuard) -> Self {
+        Guard {
+            _backend: Some(guard),
+            ..Vec::new().into()
+        }
+    }
+}
+
+impl From<Worker> for Guard {
+    fn from(worker: Worker) -> Self {
+        vec![worker].into()
+    }
+}
+
+/// Get the current power state of the system from the backend selected by
+/// [`crate::BACKEND_ENV_VAR`], `/sys/class/power_supply` by default.
 pub fn get_current_power_state() -> Result<Status, crate::Error> {
-    Err(crate::Error::Linux)
+    crate::default_backend()?.status()
+}
+
+/// The status of `sysfs`, with power saving mode from power profiles.
+fn sysfs_status(sysfs: &Sysfs) -> Result<Status, crate::Error> {
+    #[allow(unused_mut)]
+    let mut status = sysfs.status()?;
+    #[cfg(feature = "dbus")]
+    if let Some(profiles) = sysfs.power_profiles() {
+        status.power_saving_mode = profiles.is_power_saver();
+    }
+    Ok(status)
+}
+
+/// Get the batteries of peripheral devices from `/sys/class/power_supply`.
+pub fn get_peripheral_batteries() -> Result<Vec<PeripheralBattery>, crate::Error> {
+    Sysfs::default().peripheral_batteries()
+}
+
+fn notify_status<T, F>(callback: &F, status: Result<T, crate::Error>)
+where
+    F: Fn(Result<T, crate::Error>),
+{
+    let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| callback(status)));
+}
+
+/// Calls `on_change` with `Ok(())` whenever a power supply may have changed.
+fn run_uevent_loop<F>(socket: UeventSocket, stop: &EventFd, mut on_change: F)
+where
+    F: FnMut(Result<(), crate::Error>),
+{
+    loop {
+        match stop.wait_readable(socket.as_raw_fd()) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                on_change(Err(Error::FailedToReceiveUevent(e).into()));
+                break;
+            }
+        }
+
+        match socket.recv() {
+            Ok(Some(uevent)) if uevent.subsystem() == Some(POWER_SUPPLY_SUBSYSTEM) => {
+                log::trace!("power supply uevent: {} {}", uevent.action, uevent.devpath);
+                on_change(Ok(()));
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            // The socket buffer overflowed and events were lost, so refresh anyway.
+            Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                log::warn!("uevent socket overflowed: {e}");
+                on_change(Ok(()));
+            }
+            Err(e) => {
+                on_change(Err(Error::FailedToReceiveUevent(e).into()));
+                break;
+            }
+        }
+    }
+}
+
+fn spawn_uevent_worker<F>(on_change: F) -> Result<Worker, crate::Error>
+where
+    F: FnMut(Result<(), crate::Error>) + Send + 'static,
+{
+    let stop = Arc::new(EventFd::new().map_err(Error::FailedToCreateEventFd)?);
+
+    let (tx, rx) = oneshot::channel();
+    let thread = thread::Builder::new()
+        .name("powerstate-linux-uevent".to_string())
+        .spawn({
+            let stop = stop.clone();
+            move || {
+                let socket = match UeventSocket::open() {
+                    Ok(socket) => {
+                        let _ = tx.send(Ok(()));
+                        socket
+                    }
+                    Err(e) => {
+                        let _ = tx.send(Err(Error::FailedToOpenUeventSocket(e)));
+                        return;
+                    }
+                };
+
+                run_uevent_loop(socket, &stop, on_change);
+            }
+        })
+        .map_err(crate::Error::CallbackThreadSpawnFailed)?;
+
+    rx.recv()??;
+    Ok(Worker {
+        stop: Stop::EventFd(stop),
+        thread: Some(thread),
+    })
 }
 
 pub fn register_power_state_change_callback<F>(cb: F) -> Result<Guard, crate::Error>
 where
     F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
 {
-    let _ = cb;
-    Err(crate::Error::Linux)
+    register_power_state_change_callback_with(WatchOptions::default(), cb)
+}
+
+/// Like [`register_power_state_change_callback`], but only for the changes selected by
+/// `options`.
+///
+/// Registers with the backend selected by [`crate::BACKEND_ENV_VAR`]. With sysfs, power supply
+/// uevents are only watched for changes of the power supplies, and power profiles only for power
+/// saving mode.
+pub fn register_power_state_change_callback_with<F>(
+    options: WatchOptions,
+    cb: F,
+) -> Result<Guard, crate::Error>
+where
+    F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
+{
+    let guard = crate::default_backend()?.subscribe(options, Box::new(cb))?;
+    Ok(guard.into())
+}
+
+fn register_sysfs_callback<F>(
+    sysfs: Sysfs,
+    options: WatchOptions,
+    cb: F,
+) -> Result<Guard, crate::Error>
+where
+    F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
+{
+    let (cb, coalescer) = coalesce(
+        options.coalesce,
+        watch::filter(&options, || sysfs_status(&sysfs), cb),
+    )?;
+    let cb: Arc<OnPowerStateChange> = Arc::new(cb);
+    let mut workers = vec![];
+    let mut polling = None;
+    if options.watches_power_supplies() {
+        match spawn_uevent_worker({
+            let cb = cb.clone();
+            let sysfs = sysfs.clone();
+            move |changed| notify_status(cb.as_ref(), changed.and_then(|()| sysfs_status(&sysfs)))
+        }) {
+            Ok(worker) => workers.push(worker),
+            // E.g. sandboxes without access to netlink sockets.
+            Err(crate::Error::Linux(Error::FailedToOpenUeventSocket(e))) => {
+                log::warn!(
+                    "uevents are unavailable ({e}), polling every {DEFAULT_POLLING_INTERVAL:?} instead"
+                );
+                let cb = cb.clone();
+                let sysfs = sysfs.clone();
+                polling = Some(spawn_poller(
+                    DEFAULT_POLLING_INTERVAL,
+                    move || sysfs_status(&sysfs),
+                    move |status| cb(status),
+                )?);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Power saving mode changes are not reported through uevents.
+    #[cfg(feature = "dbus")]
+    if options.saver_mode
+        && let Some(profiles) = sysfs.power_profiles().cloned()
+    {
+        match profiles.spawn_profile_worker(move |_| {
+            notify_status(cb.as_ref(), sysfs_status(&sysfs));
+        }) {
+            Ok(worker) => workers.push(worker),
+            Err(e) => log::debug!("power profile changes are unavailable: {e}"),
+        }
+    }
+
+    Ok(Guard {
+        _backend: None,
+        _workers: workers,
+        _polling: polling,
+        _coalescer: coalescer,
+    })
+}
+
+/// Invokes the callback whenever the batteries of peripheral devices change, e.g. when a wireless
+/// mouse is connected or its charge level drops.
+pub fn register_peripheral_battery_change_callback<F>(cb: F) -> Result<Guard, crate::Error>
+where
+    F: Fn(Result<Vec<PeripheralBattery>, crate::Error>) + Send + Sync + 'static,
+{
+    let mut previous = get_peripheral_batteries().ok();
+    let worker = spawn_uevent_worker(move |changed| match changed {
+        Ok(()) => match get_peripheral_batteries() {
+            // Uevents of system batteries are delivered as well, so only report actual changes.
+            Ok(batteries) if previous.as_ref() == Some(&batteries) => {}
+            Ok(batteries) => {
+                previous = Some(batteries.clone());
+                notify_status(&cb, Ok(batteries));
+            }
+            Err(e) => notify_status(&cb, Err(e)),
+        },
+        Err(e) => notify_status(&cb, Err(e)),
+    })?;
+    Ok(worker.into())
+}
+
+/// Invokes the callback before the system goes to sleep and after it wakes up, as reported by
+/// systemd-logind.
+pub fn register_sleep_callback<F>(options: SleepOptions, cb: F) -> Result<Guard, crate::Error>
+where
+    F: Fn(SleepEvent) + Send + Sync + 'static,
+{
+    #[cfg(feature = "dbus")]
+    return Logind::system()?.register_sleep_callback(options, cb);
+
+    #[cfg(not(feature = "dbus"))]
+    {
+        let _ = (options, cb);
+        Err(crate::Error::SleepEventsNotSupported)
+    }
+}
+
+#[cfg(feature = "dbus")]
+pub(crate) const BACKEND_NAMES: &[&str] = &["sysfs", "upower"];
+#[cfg(not(feature = "dbus"))]
+pub(crate) const BACKEND_NAMES: &[&str] = &["sysfs"];
+
+pub(crate) fn backend(name: &str) -> Option<Result<Box<dyn PowerBackend>, crate::Error>> {
+    match name {
+        "sysfs" => Some(Ok(Box::new(Sysfs::default()))),
+        #[cfg(feature = "dbus")]
+        "upower" => Some(UPower::system().map(|upower| Box::new(upower) as _)),
+        _ => None,
+    }
+}
+
+impl PowerBackend for Sysfs {
+    fn name(&self) -> &str {
+        "sysfs"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let capabilities = Capabilities::NOTIFICATIONS
+            | Capabilities::TIME_REMAINING
+            | Capabilities::POWER_SOURCES
+            | Capabilities::PERIPHERAL_BATTERIES;
+        #[cfg(feature = "dbus")]
+        let capabilities = capabilities | Capabilities::SAVER_MODE;
+        capabilities
+    }
+
+    fn status(&self) -> Result<Status, crate::Error> {
+        sysfs_status(self)
+    }
+
+    fn subscribe(
+        &self,
+        options: WatchOptions,
+        cb: OnPowerStateChange,
+    ) -> Result<BackendGuard, crate::Error> {
+        register_sysfs_callback(self.clone(), options, cb).map(BackendGuard::new)
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl PowerBackend for UPower {
+    fn name(&self) -> &str {
+        "upower"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::all()
+    }
+
+    fn status(&self) -> Result<Status, crate::Error> {
+        UPower::status(self)
+    }
+
+    fn subscribe(
+        &self,
+        options: WatchOptions,
+        cb: OnPowerStateChange,
+    ) -> Result<BackendGuard, crate::Error> {
+        self.register_power_state_change_callback_with(options, cb)
+            .map(BackendGuard::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_and_drop_guard() {
+        let guard = register_power_state_change_callback(|status| {
+            println!("{status:#?}");
+        })
+        .unwrap();
+        drop(guard);
+    }
 }
@@ -0,0 +1,542 @@
+//! ACPI events from acpid or from the kernel's `acpi_event` generic netlink family.
+
+use std::{
+    io::{self, Read},
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::net::UnixStream,
+    },
+    path::Path,
+    sync::Arc,
+    thread,
+};
+
+use crate::{CoalesceOptions, OnPowerStateChange, Status, coalesce::coalesce};
+
+use super::{Error, EventFd, Guard, Stop, Worker, get_current_power_state, notify_status};
+
+pub const DEFAULT_ACPID_SOCKET_PATH: &str = "/var/run/acpid.socket";
+
+const ACPI_GENL_FAMILY_NAME: &str = "acpi_event";
+const ACPI_GENL_MCAST_GROUP_NAME: &str = "acpi_mc_group";
+const ACPI_GENL_ATTR_EVENT: u16 = 1;
+/// Sizes of the fields of `struct acpi_genl_event`.
+const ACPI_DEVICE_CLASS_SIZE: usize = 20;
+const ACPI_BUS_ID_SIZE: usize = 15;
+
+const NLMSG_HDRLEN: usize = size_of::<libc::nlmsghdr>();
+const GENL_HDRLEN: usize = size_of::<libc::genlmsghdr>();
+const NLA_HDRLEN: usize = size_of::<libc::nlattr>();
+const NETLINK_BUFFER_SIZE: usize = 8192;
+
+/// An ACPI event, e.g. `ac_adapter ACPI0003:00 00000080 00000001`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct AcpiEvent {
+    pub device_class: String,
+    pub bus_id: String,
+    pub kind: u32,
+    pub data: u32,
+}
+
+impl AcpiEvent {
+    /// Parses a line sent by acpid.
+    pub fn parse_acpid_line(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let device_class = fields.next()?.to_string();
+        let bus_id = fields.next()?.to_string();
+        let kind = u32::from_str_radix(fields.next()?, 16).ok()?;
+        let data = u32::from_str_radix(fields.next()?, 16).ok()?;
+        Some(AcpiEvent {
+            device_class,
+            bus_id,
+            kind,
+            data,
+        })
+    }
+
+    /// Parses the payload of an `ACPI_GENL_ATTR_EVENT` attribute, i.e. a
+    /// `struct acpi_genl_event`.
+    pub fn parse_genl_event(payload: &[u8]) -> Option<Self> {
+        let c_string = |bytes: &[u8]| {
+            let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+        let u32_at = |offset: usize| {
+            let bytes = payload.get(offset..offset + 4)?;
+            Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+        };
+
+        // `type` and `data` follow the strings, aligned to 4 bytes.
+        let offset = align(ACPI_DEVICE_CLASS_SIZE + ACPI_BUS_ID_SIZE);
+        Some(AcpiEvent {
+            device_class: c_string(payload.get(..ACPI_DEVICE_CLASS_SIZE)?),
+            bus_id: c_string(payload.get(ACPI_DEVICE_CLASS_SIZE..offset)?),
+            kind: u32_at(offset)?,
+            data: u32_at(offset + 4)?,
+        })
+    }
+
+    /// Whether the event is about an AC adapter or a battery.
+    pub fn is_power_supply_event(&self) -> bool {
+        self.device_class.starts_with("ac_adapter") || self.device_class.starts_with("battery")
+    }
+}
+
+/// Rounds `len` up to the 4 bytes alignment of netlink messages and attributes.
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Iterates over `(type, payload)` of netlink attributes.
+fn attributes(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        let len = u16::from_ne_bytes(buf.get(0..2)?.try_into().ok()?) as usize;
+        let kind = u16::from_ne_bytes(buf.get(2..4)?.try_into().ok()?);
+        if len < NLA_HDRLEN {
+            return None;
+        }
+        let payload = buf.get(NLA_HDRLEN..len)?;
+        buf = buf.get(align(len)..).unwrap_or_default();
+        Some((kind & libc::NLA_TYPE_MASK as u16, payload))
+    })
+}
+
+/// Iterates over `(type, payload)` of the netlink messages in a datagram.
+fn messages(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        let len = u32::from_ne_bytes(buf.get(0..4)?.try_into().ok()?) as usize;
+        let kind = u16::from_ne_bytes(buf.get(4..6)?.try_into().ok()?);
+        if len < NLMSG_HDRLEN {
+            return None;
+        }
+        let payload = buf.get(NLMSG_HDRLEN..len)?;
+        buf = buf.get(align(len)..).unwrap_or_default();
+        Some((kind, payload))
+    })
+}
+
+/// A source of ACPI events that can be waited on with `poll`.
+trait AcpiEventSource: AsRawFd + Send + 'static {
+    /// Reads the events that are available, failing with `UnexpectedEof` once closed.
+    fn read_events(&mut self) -> io::Result<Vec<AcpiEvent>>;
+}
+
+struct AcpidSocket {
+    stream: UnixStream,
+    buf: Vec<u8>,
+}
+
+impl AcpidSocket {
+    fn connect(path: &Path) -> io::Result<Self> {
+        Ok(AcpidSocket {
+            stream: UnixStream::connect(path)?,
+            buf: vec![],
+        })
+    }
+}
+
+impl AsRawFd for AcpidSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl AcpiEventSource for AcpidSocket {
+    fn read_events(&mut self) -> io::Result<Vec<AcpiEvent>> {
+        let mut chunk = [0u8; 1024];
+        let len = self.stream.read(&mut chunk)?;
+        if len == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.buf.extend_from_slice(&chunk[..len]);
+
+        let mut events = vec![];
+        while let Some(end) = self.buf.iter().position(|b| *b == b'\n') {
+            let line = self.buf.drain(..=end).collect::<Vec<_>>();
+            if let Some(event) = AcpiEvent::parse_acpid_line(&String::from_utf8_lossy(&line)) {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// A generic netlink socket subscribed to the `acpi_event` family.
+struct AcpiNetlinkSocket {
+    fd: OwnedFd,
+    family_id: u16,
+}
+
+impl AcpiNetlinkSocket {
+    fn open() -> Result<Self, Error> {
+        let fd = unsafe {
+            let fd = libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_GENERIC,
+            );
+            if fd < 0 {
+                return Err(Error::FailedToOpenAcpiNetlinkSocket(
+                    io::Error::last_os_error(),
+                ));
+            }
+            OwnedFd::from_raw_fd(fd)
+        };
+        let mut socket = AcpiNetlinkSocket { fd, family_id: 0 };
+
+        let (family_id, group_id) = socket
+            .resolve_family()
+            .map_err(Error::FailedToOpenAcpiNetlinkSocket)?
+            .ok_or(Error::AcpiNetlinkFamilyNotFound)?;
+        socket.family_id = family_id;
+
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.fd.as_raw_fd(),
+                libc::SOL_NETLINK,
+                libc::NETLINK_ADD_MEMBERSHIP,
+                &group_id as *const u32 as *const libc::c_void,
+                size_of::<u32>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::FailedToOpenAcpiNetlinkSocket(
+                io::Error::last_os_error(),
+            ));
+        }
+        Ok(socket)
+    }
+
+    fn recv(&self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; NETLINK_BUFFER_SIZE];
+        let len = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        buf.truncate(len as usize);
+        Ok(buf)
+    }
+
+    /// Asks the generic netlink controller for the ids of the `acpi_event` family and its
+    /// multicast group.
+    fn resolve_family(&self) -> io::Result<Option<(u16, u32)>> {
+        let name = format!("{ACPI_GENL_FAMILY_NAME}\0");
+        let attr_len = NLA_HDRLEN + name.len();
+        let msg_len = NLMSG_HDRLEN + GENL_HDRLEN + align(attr_len);
+
+        let mut request = Vec::with_capacity(msg_len);
+        request.extend_from_slice(&(msg_len as u32).to_ne_bytes());
+        request.extend_from_slice(&(libc::GENL_ID_CTRL as u16).to_ne_bytes());
+        request.extend_from_slice(&(libc::NLM_F_REQUEST as u16).to_ne_bytes());
+        request.extend_from_slice(&1u32.to_ne_bytes());
+        request.extend_from_slice(&0u32.to_ne_bytes());
+        request.extend_from_slice(&[libc::CTRL_CMD_GETFAMILY as u8, 1, 0, 0]);
+        request.extend_from_slice(&(attr_len as u16).to_ne_bytes());
+        request.extend_from_slice(&(libc::CTRL_ATTR_FAMILY_NAME as u16).to_ne_bytes());
+        request.extend_from_slice(name.as_bytes());
+        request.resize(msg_len, 0);
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        let ret = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                request.as_ptr() as *const libc::c_void,
+                request.len(),
+                0,
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let response = self.recv()?;
+        for (kind, payload) in messages(&response) {
+            if kind == libc::NLMSG_ERROR as u16 {
+                // The family is missing when the kernel was built without ACPI.
+                return Ok(None);
+            }
+            if kind != libc::GENL_ID_CTRL as u16 {
+                continue;
+            }
+            let Some(attrs) = payload.get(GENL_HDRLEN..) else {
+                continue;
+            };
+            return Ok(parse_family(attrs));
+        }
+        Ok(None)
+    }
+}
+
+/// Extracts the family id and the id of the ACPI multicast group from the attributes of a
+/// `CTRL_CMD_NEWFAMILY` message.
+fn parse_family(attrs: &[u8]) -> Option<(u16, u32)> {
+    let mut family_id = None;
+    let mut group_id = None;
+    for (kind, payload) in attributes(attrs) {
+        match kind as libc::c_int {
+            libc::CTRL_ATTR_FAMILY_ID => {
+                family_id = Some(u16::from_ne_bytes(payload.get(..2)?.try_into().ok()?));
+            }
+            libc::CTRL_ATTR_MCAST_GROUPS => {
+                for (_, group) in attributes(payload) {
+                    let mut name = None;
+                    let mut id = None;
+                    for (kind, value) in attributes(group) {
+                        match kind as libc::c_int {
+                            libc::CTRL_ATTR_MCAST_GRP_NAME => {
+                                name = Some(value.strip_suffix(&[0]).unwrap_or(value));
+                            }
+                            libc::CTRL_ATTR_MCAST_GRP_ID => {
+                                id = Some(u32::from_ne_bytes(value.get(..4)?.try_into().ok()?));
+                            }
+                            _ => {}
+                        }
+                    }
+                    if name == Some(ACPI_GENL_MCAST_GROUP_NAME.as_bytes()) {
+                        group_id = id;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Some((family_id?, group_id?))
+}
+
+impl AsRawFd for AcpiNetlinkSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AcpiEventSource for AcpiNetlinkSocket {
+    fn read_events(&mut self) -> io::Result<Vec<AcpiEvent>> {
+        let datagram = self.recv()?;
+        let events = messages(&datagram)
+            .filter(|(kind, _)| *kind == self.family_id)
+            .filter_map(|(_, payload)| payload.get(GENL_HDRLEN..))
+            .flat_map(attributes)
+            .filter(|(kind, _)| *kind == ACPI_GENL_ATTR_EVENT)
+            .filter_map(|(_, payload)| AcpiEvent::parse_genl_event(payload))
+            .collect();
+        Ok(events)
+    }
+}
+
+fn spawn_acpi_worker<S, F>(
+    name: &str,
+    mut source: S,
+    mut on_event: F,
+) -> Result<Worker, crate::Error>
+where
+    S: AcpiEventSource,
+    F: FnMut(io::Result<AcpiEvent>) + Send + 'static,
+{
+    let stop = Arc::new(EventFd::new().map_err(Error::FailedToCreateEventFd)?);
+    let thread = thread::Builder::new()
+        .name(name.to_string())
+        .spawn({
+            let stop = stop.clone();
+            move || {
+                loop {
+                    match stop.wait_readable(source.as_raw_fd()) {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(e) => {
+                            on_event(Err(e));
+                            break;
+                        }
+                    }
+                    match source.read_events() {
+                        Ok(events) => events.into_iter().for_each(|event| on_event(Ok(event))),
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => {
+                            on_event(Err(e));
+                            break;
+                        }
+                    }
+                }
+            }
+        })
+        .map_err(crate::Error::CallbackThreadSpawnFailed)?;
+
+    Ok(Worker {
+        stop: Stop::EventFd(stop),
+        thread: Some(thread),
+    })
+}
+
+/// Refreshes the status whenever an AC adapter or battery event arrives.
+fn power_supply_events(cb: OnPowerStateChange) -> impl FnMut(io::Result<AcpiEvent>) + Send {
+    move |event| match event {
+        Ok(event) if event.is_power_supply_event() => {
+            log::trace!("ACPI event: {event:?}");
+            notify_status(&cb, get_current_power_state());
+        }
+        Ok(_) => {}
+        Err(e) => notify_status(&cb, Err(Error::FailedToReceiveAcpiEvent(e).into())),
+    }
+}
+
+/// Invokes the callback on AC adapter and battery events reported by acpid, for systems that
+/// run acpid but not UPower.
+pub fn register_acpid_callback<F>(
+    socket_path: impl AsRef<Path>,
+    cb: F,
+) -> Result<Guard, crate::Error>
+where
+    F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
+{
+    let socket_path = socket_path.as_ref();
+    let socket = AcpidSocket::connect(socket_path)
+        .map_err(|e| Error::FailedToConnectAcpid(socket_path.to_path_buf(), e))?;
+    let (cb, coalescer) = coalesce(Some(CoalesceOptions::default()), cb)?;
+    let worker = spawn_acpi_worker("powerstate-linux-acpid", socket, power_supply_events(cb))?;
+    Ok(Guard::from(worker).with_coalescer(coalescer))
+}
+
+/// Invokes the callback on AC adapter and battery events of the kernel's `acpi_event` generic
+/// netlink family.
+pub fn register_acpi_netlink_callback<F>(cb: F) -> Result<Guard, crate::Error>
+where
+    F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
+{
+    let socket = AcpiNetlinkSocket::open()?;
+    let (cb, coalescer) = coalesce(Some(CoalesceOptions::default()), cb)?;
+    let worker = spawn_acpi_worker("powerstate-linux-acpi", socket, power_supply_events(cb))?;
+    Ok(Guard::from(worker).with_coalescer(coalescer))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, os::unix::net::UnixListener, sync::mpsc, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_acpid_line() {
+        let event =
+            AcpiEvent::parse_acpid_line("ac_adapter ACPI0003:00 00000080 00000001\n").unwrap();
+        assert_eq!(event.device_class, "ac_adapter");
+        assert_eq!(event.bus_id, "ACPI0003:00");
+        assert_eq!(event.kind, 0x80);
+        assert_eq!(event.data, 1);
+        assert!(event.is_power_supply_event());
+
+        let event = AcpiEvent::parse_acpid_line("button/power PBTN 00000080 00000000").unwrap();
+        assert!(!event.is_power_supply_event());
+
+        assert!(AcpiEvent::parse_acpid_line("ac_adapter ACPI0003:00").is_none());
+    }
+
+    #[test]
+    fn test_parse_genl_event() {
+        let mut payload = vec![0u8; 44];
+        payload[..7].copy_from_slice(b"battery");
+        payload[20..27].copy_from_slice(b"PNP0C0A");
+        payload[36..40].copy_from_slice(&0x80u32.to_ne_bytes());
+        payload[40..44].copy_from_slice(&1u32.to_ne_bytes());
+
+        let event = AcpiEvent::parse_genl_event(&payload).unwrap();
+        assert_eq!(event.device_class, "battery");
+        assert_eq!(event.bus_id, "PNP0C0A");
+        assert_eq!(event.kind, 0x80);
+        assert_eq!(event.data, 1);
+        assert!(AcpiEvent::parse_genl_event(&payload[..40]).is_none());
+    }
+
+    #[test]
+    fn test_parse_family() {
+        let attr = |kind: u16, payload: &[u8]| {
+            let mut attr = vec![];
+            attr.extend_from_slice(&((NLA_HDRLEN + payload.len()) as u16).to_ne_bytes());
+            attr.extend_from_slice(&kind.to_ne_bytes());
+            attr.extend_from_slice(payload);
+            attr.resize(align(attr.len()), 0);
+            attr
+        };
+        let group = [
+            attr(libc::CTRL_ATTR_MCAST_GRP_NAME as u16, b"acpi_mc_group\0"),
+            attr(libc::CTRL_ATTR_MCAST_GRP_ID as u16, &7u32.to_ne_bytes()),
+        ]
+        .concat();
+        let attrs = [
+            attr(libc::CTRL_ATTR_FAMILY_NAME as u16, b"acpi_event\0"),
+            attr(libc::CTRL_ATTR_FAMILY_ID as u16, &0x1cu16.to_ne_bytes()),
+            attr(
+                libc::CTRL_ATTR_MCAST_GROUPS as u16 | libc::NLA_F_NESTED as u16,
+                &attr(1 | libc::NLA_F_NESTED as u16, &group),
+            ),
+        ]
+        .concat();
+
+        assert_eq!(parse_family(&attrs), Some((0x1c, 7)));
+        assert_eq!(parse_family(&attrs[..20]), None);
+    }
+
+    #[test]
+    fn test_acpid_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("acpid.socket");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let socket = AcpidSocket::connect(&path).unwrap();
+        let _worker = spawn_acpi_worker("powerstate-test-acpid", socket, move |event| {
+            let _ = tx.send(event.map_err(|e| e.kind()));
+        })
+        .unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .write_all(b"button/power PBTN 00000080 00000000\nac_adapter ACPI0003:00 0000")
+            .unwrap();
+        stream.flush().unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        stream.write_all(b"0080 00000000\n").unwrap();
+
+        let timeout = Duration::from_secs(5);
+        let event = rx.recv_timeout(timeout).unwrap().unwrap();
+        assert_eq!(event.device_class, "button/power");
+        let event = rx.recv_timeout(timeout).unwrap().unwrap();
+        assert_eq!(event.device_class, "ac_adapter");
+        assert_eq!(event.kind, 0x80);
+
+        drop(stream);
+        assert_eq!(
+            rx.recv_timeout(timeout).unwrap().unwrap_err(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_register_acpid_callback() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("acpid.socket");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _guard = register_acpid_callback(&path, move |status| {
+            let _ = tx.send(status.is_ok());
+        })
+        .unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .write_all(b"button/lid LID close\nbattery PNP0C0A:00 00000080 00000001\n")
+            .unwrap();
+        assert!(rx.recv_timeout(Duration::from_secs(5)).unwrap());
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use std::{collections::HashMap, sync::Mutex, thread};
+
+use futures_lite::{StreamExt, future};
+use zbus::{
+    MatchRule, Message, MessageStream,
+    blocking::{Connection, fdo::PropertiesProxy},
+    message,
+    names::InterfaceName,
+    zvariant::{ObjectPath, OwnedValue},
+};
+
+use super::{Error, Stop, Worker};
+
+/// A connection to the system bus shared by all backends.
+///
+/// Failed attempts are not cached, so the bus is retried when it becomes available later.
+pub(super) fn system_connection() -> Result<Connection, Error> {
+    static CONNECTION: Mutex<Option<Connection>> = Mutex::new(None);
+    let mut connection = CONNECTION.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(connection) = connection.as_ref() {
+        return Ok(connection.clone());
+    }
+    let new_connection = Connection::system()?;
+    *connection = Some(new_connection.clone());
+    Ok(new_connection)
+}
+
+pub(super) fn connect(address: &str) -> Result<Connection, Error> {
+    Ok(zbus::blocking::connection::Builder::address(address)?.build()?)
+}
+
+/// Properties of a single D-Bus object, as returned by `GetAll`.
+pub(super) struct Properties(HashMap<String, OwnedValue>);
+
+impl Properties {
+    pub fn get_all(
+        connection: &Connection,
+        service: &str,
+        path: ObjectPath<'_>,
+        interface: &str,
+    ) -> Result<Self, Error> {
+        let proxy = PropertiesProxy::builder(connection)
+            .destination(service)?
+            .path(path)?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()?;
+        let interface = InterfaceName::try_from(interface).map_err(zbus::Error::from)?;
+        let properties = proxy.get_all(interface).map_err(zbus::Error::from)?;
+        Ok(Properties(properties))
+    }
+
+    pub fn get<'a, T>(&'a self, name: &str) -> Option<T>
+    where
+        T: TryFrom<&'a OwnedValue>,
+    {
+        self.0.get(name).and_then(|value| T::try_from(value).ok())
+    }
+
+    pub fn string(&self, name: &str) -> Option<String> {
+        self.get::<&str>(name)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    }
+}
+
+/// A match rule for `PropertiesChanged` signals emitted below `path`.
+pub(super) fn properties_changed_rule(path: &str) -> Result<MatchRule<'_>, Error> {
+    Ok(MatchRule::builder()
+        .msg_type(message::Type::Signal)
+        .interface("org.freedesktop.DBus.Properties")?
+        .member("PropertiesChanged")?
+        .path_namespace(path)?
+        .build())
+}
+
+/// Spawns a thread invoking `on_message` for every message matching `rule`.
+pub(super) fn spawn_signal_worker<F>(
+    name: &str,
+    connection: &Connection,
+    rule: MatchRule<'_>,
+    mut on_message: F,
+) -> Result<Worker, crate::Error>
+where
+    F: FnMut(zbus::Result<Message>) + Send + 'static,
+{
+    let mut stream = zbus::block_on(MessageStream::for_match_rule(
+        rule,
+        connection.inner(),
+        None,
+    ))
+    .map_err(Error::DBus)?;
+
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+    let thread = thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            let messages = async {
+                while let Some(message) = stream.next().await {
+                    on_message(message);
+                }
+            };
+            let stopped = async {
+                let _ = stop_rx.await;
+            };
+            zbus::block_on(future::or(stopped, messages));
+        })
+        .map_err(crate::Error::CallbackThreadSpawnFailed)?;
+
+    Ok(Worker {
+        stop: Stop::Channel(Some(stop_tx)),
+        thread: Some(thread),
+    })
+}
@@ -0,0 +1,215 @@
+//! System sleep notifications from systemd-logind.
+//! Ref: https://www.freedesktop.org/software/systemd/man/latest/org.freedesktop.login1.html
+
+use std::panic;
+
+use zbus::{
+    MatchRule,
+    blocking::{Connection, Proxy},
+    message,
+    zvariant::OwnedFd,
+};
+
+use crate::{OnSleepEvent, SleepEvent, SleepOptions};
+
+use super::{Error, Guard, dbus};
+
+const LOGIND_SERVICE: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+
+const INHIBITOR_WHO: &str = "powerstate";
+const INHIBITOR_WHY: &str = "Running sleep callbacks";
+
+/// Receives `PrepareForSleep` signals from systemd-logind.
+#[derive(Clone)]
+pub struct Logind {
+    connection: Connection,
+}
+
+impl Logind {
+    /// Connects to logind on the system bus.
+    pub fn system() -> Result<Self, crate::Error> {
+        Ok(Self {
+            connection: dbus::system_connection()?,
+        })
+    }
+
+    /// Connects to logind on the bus at the given D-Bus address.
+    pub fn with_address(address: &str) -> Result<Self, crate::Error> {
+        Ok(Self {
+            connection: dbus::connect(address)?,
+        })
+    }
+
+    fn manager(&self) -> Result<Proxy<'static>, Error> {
+        Ok(zbus::blocking::proxy::Builder::new(&self.connection)
+            .destination(LOGIND_SERVICE)?
+            .path(LOGIND_PATH)?
+            .interface(MANAGER_INTERFACE)?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()?)
+    }
+
+    /// Takes a delay inhibitor lock, which holds off sleep until the returned fd is closed.
+    fn inhibit_sleep(&self) -> Result<OwnedFd, Error> {
+        Ok(self
+            .manager()?
+            .call::<_, _, OwnedFd>("Inhibit", &("sleep", INHIBITOR_WHO, INHIBITOR_WHY, "delay"))?)
+    }
+
+    /// Invokes the callback before the system goes to sleep and after it wakes up.
+    ///
+    /// With [`SleepOptions::delay_sleep`], sleep is delayed until the callback for
+    /// [`SleepEvent::WillSleep`] returns, at most for logind's `InhibitDelayMaxSec`.
+    pub fn register_sleep_callback<F>(
+        &self,
+        options: SleepOptions,
+        cb: F,
+    ) -> Result<Guard, crate::Error>
+    where
+        F: Fn(SleepEvent) + Send + Sync + 'static,
+    {
+        let cb: OnSleepEvent = Box::new(cb);
+        let mut inhibitor = if options.delay_sleep {
+            Some(self.inhibit_sleep()?)
+        } else {
+            None
+        };
+
+        let rule = MatchRule::builder()
+            .msg_type(message::Type::Signal)
+            .interface(MANAGER_INTERFACE)
+            .and_then(|rule| rule.member("PrepareForSleep"))
+            .and_then(|rule| rule.path(LOGIND_PATH))
+            .map_err(Error::DBus)?
+            .build();
+
+        let logind = self.clone();
+        let worker = dbus::spawn_signal_worker(
+            "powerstate-linux-logind",
+            &self.connection,
+            rule,
+            move |message| {
+                let start = match message.and_then(|message| message.body().deserialize::<bool>()) {
+                    Ok(start) => start,
+                    Err(e) => {
+                        log::warn!("failed to receive PrepareForSleep signal: {e}");
+                        return;
+                    }
+                };
+
+                let event = if start {
+                    SleepEvent::WillSleep
+                } else {
+                    SleepEvent::DidWake
+                };
+                // Take the lock again before anything else, so the next sleep is delayed as well.
+                if event == SleepEvent::DidWake && options.delay_sleep && inhibitor.is_none() {
+                    match logind.inhibit_sleep() {
+                        Ok(fd) => inhibitor = Some(fd),
+                        Err(e) => log::warn!("failed to take sleep inhibitor lock: {e}"),
+                    }
+                }
+
+                let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| cb(event)));
+
+                if event == SleepEvent::WillSleep {
+                    // Closing the fd releases the lock and lets the system go to sleep.
+                    inhibitor = None;
+                }
+            },
+        )?;
+        Ok(worker.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{PipeReader, Read},
+        sync::mpsc,
+        time::Duration,
+    };
+
+    use zbus::object_server::SignalEmitter;
+
+    use super::*;
+    use crate::os_impl::linux::test_bus::TestBus;
+
+    #[derive(Default)]
+    struct FakeLogind {
+        inhibitors: Vec<(String, String, PipeReader)>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl FakeLogind {
+        fn inhibit(&mut self, what: String, _who: String, _why: String, mode: String) -> OwnedFd {
+            let (reader, writer) = std::io::pipe().unwrap();
+            self.inhibitors.push((what, mode, reader));
+            std::os::fd::OwnedFd::from(writer).into()
+        }
+
+        #[zbus(signal)]
+        async fn prepare_for_sleep(emitter: &SignalEmitter<'_>, start: bool) -> zbus::Result<()>;
+    }
+
+    fn prepare_for_sleep(service: &Connection, start: bool) {
+        zbus::block_on(async {
+            let emitter = SignalEmitter::new(service.inner(), LOGIND_PATH).unwrap();
+            FakeLogind::prepare_for_sleep(&emitter, start)
+                .await
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn test_sleep_callback_with_delay_inhibitor() {
+        let Some(bus) = TestBus::start() else {
+            return;
+        };
+        let service = zbus::blocking::connection::Builder::address(bus.address())
+            .unwrap()
+            .name(LOGIND_SERVICE)
+            .unwrap()
+            .serve_at(LOGIND_PATH, FakeLogind::default())
+            .unwrap()
+            .build()
+            .unwrap();
+        let iface = service
+            .object_server()
+            .interface::<_, FakeLogind>(LOGIND_PATH)
+            .unwrap();
+
+        let logind = Logind::with_address(bus.address()).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let _guard = logind
+            .register_sleep_callback(SleepOptions { delay_sleep: true }, move |event| {
+                let _ = tx.send(event);
+            })
+            .unwrap();
+        {
+            let fake = iface.get();
+            assert_eq!(fake.inhibitors.len(), 1);
+            assert_eq!(fake.inhibitors[0].0, "sleep");
+            assert_eq!(fake.inhibitors[0].1, "delay");
+        }
+
+        prepare_for_sleep(&service, true);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            SleepEvent::WillSleep
+        );
+        // The lock is released once the callback returned, which closes the pipe.
+        let mut buf = [0u8; 1];
+        let mut reader = iface.get_mut().inhibitors.remove(0).2;
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        prepare_for_sleep(&service, false);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            SleepEvent::DidWake
+        );
+        assert_eq!(iface.get().inhibitors.len(), 1);
+    }
+}
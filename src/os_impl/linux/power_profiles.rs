@@ -0,0 +1,308 @@
+//! Power profiles from power-profiles-daemon.
+//! Ref: https://gitlab.freedesktop.org/upower/power-profiles-daemon
+
+use std::collections::HashMap;
+
+use zbus::{
+    blocking::{Connection, Proxy, fdo::DBusProxy},
+    zvariant::OwnedValue,
+};
+
+use super::{Error, Guard, Worker, dbus};
+
+const SERVICE: &str = "org.freedesktop.UPower.PowerProfiles";
+const PATH: &str = "/org/freedesktop/UPower/PowerProfiles";
+/// Name used by power-profiles-daemon before 0.20.
+const LEGACY_SERVICE: &str = "net.hadess.PowerProfiles";
+const LEGACY_PATH: &str = "/net/hadess/PowerProfiles";
+
+const ACTIVE_PROFILE: &str = "ActiveProfile";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+pub enum PowerProfile {
+    #[strum(serialize = "power-saver")]
+    PowerSaver,
+    #[strum(serialize = "balanced")]
+    Balanced,
+    #[strum(serialize = "performance")]
+    Performance,
+}
+
+/// Reads and requests power profiles from power-profiles-daemon.
+///
+/// The `power-saver` profile is reported as [`crate::Status::power_saving_mode`].
+#[derive(Clone)]
+pub struct PowerProfiles {
+    connection: Connection,
+    path: &'static str,
+    /// Built once, so that reading the active profile is a single D-Bus call.
+    proxy: Proxy<'static>,
+}
+
+impl PowerProfiles {
+    /// Connects to power-profiles-daemon on the system bus.
+    pub fn system() -> Result<Self, crate::Error> {
+        Ok(Self::with_connection(dbus::system_connection()?)?)
+    }
+
+    /// Connects to power-profiles-daemon on the bus at the given D-Bus address.
+    pub fn with_address(address: &str) -> Result<Self, crate::Error> {
+        Ok(Self::with_connection(dbus::connect(address)?)?)
+    }
+
+    /// Resolves which of the service names power-profiles-daemon owns on `connection`.
+    pub(super) fn with_connection(connection: Connection) -> Result<Self, Error> {
+        let has_owner = |name: &'static str| {
+            DBusProxy::new(&connection)
+                .and_then(|proxy| Ok(proxy.name_has_owner(name.try_into()?)?))
+                .unwrap_or(false)
+        };
+        let (service, path) = if !has_owner(SERVICE) && has_owner(LEGACY_SERVICE) {
+            (LEGACY_SERVICE, LEGACY_PATH)
+        } else {
+            (SERVICE, PATH)
+        };
+
+        let proxy = zbus::blocking::proxy::Builder::new(&connection)
+            .destination(service)?
+            .path(path)?
+            .interface(service)?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()?;
+        Ok(Self {
+            connection,
+            path,
+            proxy,
+        })
+    }
+
+    pub(super) fn path(&self) -> &str {
+        self.path
+    }
+
+    pub fn active_profile(&self) -> Result<PowerProfile, crate::Error> {
+        let profile = self
+            .proxy
+            .get_property::<String>(ACTIVE_PROFILE)
+            .map_err(Error::from)?;
+        Ok(profile
+            .parse()
+            .map_err(|_| Error::UnknownPowerProfile(profile))?)
+    }
+
+    /// Whether the active profile is `power-saver`, treating an unavailable daemon as `false`.
+    pub(super) fn is_power_saver(&self) -> bool {
+        match self.active_profile() {
+            Ok(profile) => profile == PowerProfile::PowerSaver,
+            Err(e) => {
+                log::trace!("failed to read active power profile: {e}");
+                false
+            }
+        }
+    }
+
+    /// Switches the system-wide profile, which usually requires the user to be authorized.
+    pub fn set_active_profile(&self, profile: PowerProfile) -> Result<(), crate::Error> {
+        self.proxy
+            .set_property(ACTIVE_PROFILE, profile.to_string())
+            .map_err(|e| Error::DBus(e.into()))?;
+        Ok(())
+    }
+
+    /// Requests a profile until the returned hold is dropped.
+    ///
+    /// Only [`PowerProfile::PowerSaver`] and [`PowerProfile::Performance`] can be held. The
+    /// daemon also releases the hold when the D-Bus connection is closed.
+    pub fn hold_profile(
+        &self,
+        profile: PowerProfile,
+        reason: &str,
+        application_id: &str,
+    ) -> Result<ProfileHold, crate::Error> {
+        let proxy = self.proxy.clone();
+        let cookie = proxy
+            .call::<_, _, u32>(
+                "HoldProfile",
+                &(profile.to_string(), reason, application_id),
+            )
+            .map_err(Error::from)?;
+        Ok(ProfileHold { proxy, cookie })
+    }
+
+    pub(super) fn spawn_profile_worker<F>(&self, cb: F) -> Result<Worker, crate::Error>
+    where
+        F: Fn(Result<PowerProfile, crate::Error>) + Send + 'static,
+    {
+        let profiles = self.clone();
+        let rule = dbus::properties_changed_rule(self.path)?;
+        dbus::spawn_signal_worker(
+            "powerstate-linux-power-profiles",
+            &self.connection,
+            rule,
+            move |message| {
+                let message = match message {
+                    Ok(message) => message,
+                    Err(e) => return cb(Err(Error::DBus(e).into())),
+                };
+                let Ok((_, changed, invalidated)) =
+                    message
+                        .body()
+                        .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+                else {
+                    return;
+                };
+                if changed.contains_key(ACTIVE_PROFILE)
+                    || invalidated.iter().any(|name| name == ACTIVE_PROFILE)
+                {
+                    cb(profiles.active_profile());
+                }
+            },
+        )
+    }
+
+    /// Invokes the callback whenever the active profile changes.
+    pub fn register_active_profile_change_callback<F>(&self, cb: F) -> Result<Guard, crate::Error>
+    where
+        F: Fn(Result<PowerProfile, crate::Error>) + Send + Sync + 'static,
+    {
+        let worker = self.spawn_profile_worker(move |profile| {
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| cb(profile)));
+        })?;
+        Ok(worker.into())
+    }
+}
+
+/// A profile held through `HoldProfile`, released when dropped.
+pub struct ProfileHold {
+    proxy: Proxy<'static>,
+    cookie: u32,
+}
+
+impl ProfileHold {
+    pub fn cookie(&self) -> u32 {
+        self.cookie
+    }
+}
+
+impl Drop for ProfileHold {
+    fn drop(&mut self) {
+        if let Err(e) = self
+            .proxy
+            .call::<_, _, ()>("ReleaseProfile", &(self.cookie,))
+        {
+            log::warn!("failed to release power profile hold {}: {e}", self.cookie);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use super::*;
+    use crate::os_impl::linux::test_bus::TestBus;
+
+    struct FakePowerProfiles {
+        active_profile: String,
+        holds: Vec<(u32, String)>,
+        next_cookie: u32,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.UPower.PowerProfiles")]
+    impl FakePowerProfiles {
+        fn hold_profile(
+            &mut self,
+            profile: String,
+            _reason: String,
+            _application_id: String,
+        ) -> u32 {
+            self.next_cookie += 1;
+            self.holds.push((self.next_cookie, profile));
+            self.next_cookie
+        }
+
+        fn release_profile(&mut self, cookie: u32) {
+            self.holds.retain(|(held, _)| *held != cookie);
+        }
+
+        #[zbus(property)]
+        fn active_profile(&self) -> &str {
+            &self.active_profile
+        }
+
+        #[zbus(property)]
+        fn set_active_profile(&mut self, profile: String) {
+            self.active_profile = profile;
+        }
+    }
+
+    fn serve_fake_power_profiles(bus: &TestBus) -> Connection {
+        zbus::blocking::connection::Builder::address(bus.address())
+            .unwrap()
+            .name(SERVICE)
+            .unwrap()
+            .serve_at(
+                PATH,
+                FakePowerProfiles {
+                    active_profile: "balanced".to_string(),
+                    holds: vec![],
+                    next_cookie: 0,
+                },
+            )
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_active_profile() {
+        let Some(bus) = TestBus::start() else {
+            return;
+        };
+        let _service = serve_fake_power_profiles(&bus);
+
+        let profiles = PowerProfiles::with_address(bus.address()).unwrap();
+        assert_eq!(profiles.active_profile().unwrap(), PowerProfile::Balanced);
+        assert!(!profiles.is_power_saver());
+
+        let (tx, rx) = mpsc::channel();
+        let _guard = profiles
+            .register_active_profile_change_callback(move |profile| {
+                let _ = tx.send(profile.unwrap());
+            })
+            .unwrap();
+
+        profiles
+            .set_active_profile(PowerProfile::PowerSaver)
+            .unwrap();
+        assert!(profiles.is_power_saver());
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            PowerProfile::PowerSaver
+        );
+    }
+
+    #[test]
+    fn test_hold_profile() {
+        let Some(bus) = TestBus::start() else {
+            return;
+        };
+        let service = serve_fake_power_profiles(&bus);
+        let iface = service
+            .object_server()
+            .interface::<_, FakePowerProfiles>(PATH)
+            .unwrap();
+
+        let profiles = PowerProfiles::with_address(bus.address()).unwrap();
+        let hold = profiles
+            .hold_profile(PowerProfile::PowerSaver, "indexing", "powerstate-test")
+            .unwrap();
+        assert_eq!(
+            iface.get().holds,
+            vec![(hold.cookie(), "power-saver".to_string())]
+        );
+
+        drop(hold);
+        assert!(iface.get().holds.is_empty());
+    }
+}
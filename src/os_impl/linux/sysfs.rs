@@ -0,0 +1,659 @@
+#[cfg(feature = "dbus")]
+use std::sync::{Arc, OnceLock};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    BatteryId, BatteryInfo, BatteryState, BatteryTechnology, Energy, PeripheralBattery, Power,
+    PowerSource, PowerSourceKind, PowerState, Status, Temperature, Voltage,
+    aggregate::{combined_energy_percentage, combined_time_remaining},
+};
+
+use super::Error;
+#[cfg(feature = "dbus")]
+use super::PowerProfiles;
+
+/// Default location of the kernel power supply class.
+/// Ref: https://www.kernel.org/doc/Documentation/ABI/testing/sysfs-class-power
+pub const DEFAULT_POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+
+/// Values of the `type` attribute of a power supply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+enum PowerSupplyType {
+    Battery,
+    #[strum(serialize = "UPS")]
+    Ups,
+    Mains,
+    #[strum(
+        serialize = "USB",
+        serialize = "USB_DCP",
+        serialize = "USB_CDP",
+        serialize = "USB_ACA"
+    )]
+    Usb,
+    #[strum(serialize = "USB_C")]
+    UsbC,
+    #[strum(serialize = "USB_PD", serialize = "USB_PD_DRP")]
+    UsbPd,
+    Wireless,
+    Unknown,
+}
+
+/// A single entry of the power supply class, e.g. `/sys/class/power_supply/BAT0`.
+struct PowerSupply {
+    path: PathBuf,
+    kind: PowerSupplyType,
+}
+
+impl PowerSupply {
+    fn open(path: PathBuf) -> Self {
+        let mut supply = PowerSupply {
+            path,
+            kind: PowerSupplyType::Unknown,
+        };
+        if let Some(kind) = supply.attr("type") {
+            supply.kind = kind.parse().unwrap_or(PowerSupplyType::Unknown);
+        }
+        supply
+    }
+
+    fn attr(&self, name: &str) -> Option<String> {
+        let value = fs::read_to_string(self.path.join(name)).ok()?;
+        let value = value.trim();
+        if value.is_empty() {
+            None
+        } else {
+            Some(value.to_string())
+        }
+    }
+
+    fn attr_i64(&self, name: &str) -> Option<i64> {
+        self.attr(name)?.parse().ok()
+    }
+
+    fn seconds(&self, name: &str) -> Option<Duration> {
+        let secs = self.attr_i64(name)?;
+        Some(Duration::from_secs(u64::try_from(secs).ok()?))
+    }
+
+    fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Whether the supply belongs to a peripheral device, e.g. a HID or Bluetooth battery,
+    /// instead of powering the system.
+    fn is_peripheral(&self) -> bool {
+        self.attr("scope").as_deref() == Some("Device")
+    }
+
+    fn is_adapter(&self) -> bool {
+        matches!(
+            self.kind,
+            PowerSupplyType::Mains
+                | PowerSupplyType::Usb
+                | PowerSupplyType::UsbC
+                | PowerSupplyType::UsbPd
+                | PowerSupplyType::Wireless
+                | PowerSupplyType::Ups
+        )
+    }
+
+    fn online(&self) -> Option<bool> {
+        self.attr_i64("online").map(|online| online != 0)
+    }
+
+    /// Kind of the source, refined by the active entry of `usb_type` for USB supplies,
+    /// e.g. `C [PD] PD_PPS`.
+    fn power_source_kind(&self) -> PowerSourceKind {
+        let usb_type = || {
+            let usb_type = self.attr("usb_type")?;
+            let start = usb_type.find('[')?;
+            let end = usb_type[start..].find(']')?;
+            Some(usb_type[start + 1..start + end].to_string())
+        };
+        match self.kind {
+            PowerSupplyType::Mains => PowerSourceKind::Mains,
+            PowerSupplyType::Usb => match usb_type().as_deref() {
+                Some("C") => PowerSourceKind::UsbC,
+                Some(usb_type) if usb_type.starts_with("PD") => PowerSourceKind::UsbPd,
+                _ => PowerSourceKind::Usb,
+            },
+            PowerSupplyType::UsbC => PowerSourceKind::UsbC,
+            PowerSupplyType::UsbPd => PowerSourceKind::UsbPd,
+            PowerSupplyType::Wireless => PowerSourceKind::Wireless,
+            PowerSupplyType::Ups => PowerSourceKind::Ups,
+            PowerSupplyType::Battery | PowerSupplyType::Unknown => PowerSourceKind::Unknown,
+        }
+    }
+
+    fn power_source(&self) -> PowerSource {
+        PowerSource {
+            name: self.name(),
+            kind: self.power_source_kind(),
+            online: self.online(),
+            voltage_max: self.attr_i64("voltage_max").map(micro_to_unit),
+            current_max: self.attr_i64("current_max").map(micro_to_unit),
+        }
+    }
+
+    /// Energy read either from `energy_*` (µWh) or from `charge_*` (µAh) scaled by the battery
+    /// voltage.
+    fn energy(&self, energy_attr: &str, charge_attr: &str, voltage: Option<f32>) -> Option<Energy> {
+        if let Some(energy) = self.attr_i64(energy_attr) {
+            return Some(Energy::from_watt_hours(micro_to_unit(energy)));
+        }
+        let charge = self.attr_i64(charge_attr)?;
+        let voltage = voltage?;
+        Some(Energy::from_watt_hours(micro_to_unit(charge) * voltage))
+    }
+
+    /// Voltage in volts used to convert `charge_*` values into energy.
+    fn design_voltage(&self) -> Option<f32> {
+        ["voltage_min_design", "voltage_max_design", "voltage_now"]
+            .into_iter()
+            .filter_map(|name| self.attr_i64(name))
+            .find(|voltage| *voltage > 0)
+            .map(micro_to_unit)
+    }
+
+    /// The kind of the device is not exposed by sysfs, so it is always unknown.
+    fn peripheral_battery(&self) -> PeripheralBattery {
+        PeripheralBattery {
+            name: self.name(),
+            model: self.attr("model_name"),
+            kind: Default::default(),
+            percentage: self
+                .attr_i64("capacity")
+                .map(|capacity| capacity.clamp(0, 100) as u8),
+            state: self
+                .attr("status")
+                .map(|status| parse_battery_state(&status))
+                .unwrap_or_default(),
+        }
+    }
+
+    fn battery_info(&self) -> BatteryInfo {
+        let voltage_now = self.attr_i64("voltage_now").map(micro_to_unit);
+        let design_voltage = self.design_voltage();
+
+        let energy = self.energy("energy_now", "charge_now", design_voltage);
+        let energy_full = self.energy("energy_full", "charge_full", design_voltage);
+        let energy_full_design =
+            self.energy("energy_full_design", "charge_full_design", design_voltage);
+
+        let energy_rate = if let Some(power) = self.attr_i64("power_now") {
+            Some(Power::from_watts(micro_to_unit(power.abs())))
+        } else if let Some(current) = self.attr_i64("current_now")
+            && let Some(voltage) = voltage_now.or(design_voltage)
+        {
+            Some(Power::from_watts(micro_to_unit(current.abs()) * voltage))
+        } else {
+            None
+        };
+
+        let state_of_charge = match (energy, energy_full) {
+            (Some(energy), Some(energy_full)) if energy_full.joules() > 0.0 => {
+                (energy.joules() / energy_full.joules()).clamp(0.0, 1.0)
+            }
+            _ => self
+                .attr_i64("capacity")
+                .map(|capacity| (capacity as f32 / 100.0).clamp(0.0, 1.0))
+                .unwrap_or_default(),
+        };
+        let state_of_health = match (energy_full, energy_full_design) {
+            (Some(full), Some(design)) if design.joules() > 0.0 => {
+                Some((full.joules() / design.joules()).clamp(0.0, 1.0))
+            }
+            _ => None,
+        };
+
+        let state = self
+            .attr("status")
+            .map(|status| parse_battery_state(&status))
+            .unwrap_or_default();
+        let technology = self
+            .attr("technology")
+            .map(|technology| parse_technology(&technology))
+            .unwrap_or_default();
+
+        let time_to_full = self.seconds("time_to_full_now").or_else(|| {
+            match (state, energy, energy_full, energy_rate) {
+                (BatteryState::Charging, Some(energy), Some(full), Some(rate)) => {
+                    (full - energy).time_at(rate)
+                }
+                _ => None,
+            }
+        });
+        let time_to_empty =
+            self.seconds("time_to_empty_now")
+                .or_else(|| match (state, energy, energy_rate) {
+                    (BatteryState::Discharging, Some(energy), Some(rate)) => energy.time_at(rate),
+                    _ => None,
+                });
+
+        BatteryInfo {
+            id: BatteryId::new(self.name()),
+            state_of_charge,
+            energy,
+            energy_full,
+            energy_full_design,
+            energy_rate,
+            voltage: voltage_now.map(Voltage::from_volts),
+            state_of_health,
+            state,
+            technology,
+            // `temp` is reported in tenths of a degree Celsius.
+            temperature: self
+                .attr_i64("temp")
+                .map(|temp| Temperature::from_celsius(temp as f32 / 10.0)),
+            cycle_count: self
+                .attr_i64("cycle_count")
+                .and_then(|count| u32::try_from(count).ok()),
+            vendor: self.attr("manufacturer"),
+            model: self.attr("model_name"),
+            serial_number: self.attr("serial_number"),
+            time_to_full,
+            time_to_empty,
+        }
+    }
+}
+
+fn micro_to_unit(value: i64) -> f32 {
+    value as f32 / 1_000_000.0
+}
+
+fn parse_battery_state(status: &str) -> BatteryState {
+    match status {
+        "Charging" => BatteryState::Charging,
+        "Discharging" => BatteryState::Discharging,
+        "Not charging" => BatteryState::NotCharging,
+        "Full" => BatteryState::Full,
+        "Empty" => BatteryState::Empty,
+        _ => BatteryState::Unknown,
+    }
+}
+
+fn parse_technology(technology: &str) -> BatteryTechnology {
+    match technology {
+        "Li-ion" => BatteryTechnology::LithiumIon,
+        "Li-poly" => BatteryTechnology::LithiumPolymer,
+        "LiFe" => BatteryTechnology::LithiumIronPhosphate,
+        "LiMn" => BatteryTechnology::LithiumManganese,
+        "NiMH" => BatteryTechnology::NickelMetalHydride,
+        "NiCd" => BatteryTechnology::NickelCadmium,
+        _ => BatteryTechnology::Unknown,
+    }
+}
+
+/// Reads power state from a sysfs power supply class directory.
+///
+/// The root defaults to [`DEFAULT_POWER_SUPPLY_PATH`], and can be pointed at any
+/// directory with the same layout, e.g. a fake tree in tests.
+#[derive(Clone)]
+pub struct Sysfs {
+    root: PathBuf,
+    /// Resolved on first use and shared by clones, e.g. the workers of a callback.
+    #[cfg(feature = "dbus")]
+    power_profiles: Arc<OnceLock<Option<PowerProfiles>>>,
+}
+
+impl fmt::Debug for Sysfs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sysfs").field("root", &self.root).finish()
+    }
+}
+
+impl Default for Sysfs {
+    fn default() -> Self {
+        Self::new(DEFAULT_POWER_SUPPLY_PATH)
+    }
+}
+
+impl Sysfs {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            #[cfg(feature = "dbus")]
+            power_profiles: Arc::default(),
+        }
+    }
+
+    /// Power profiles on the system bus, which report power saving mode.
+    #[cfg(feature = "dbus")]
+    pub(super) fn power_profiles(&self) -> Option<&PowerProfiles> {
+        self.power_profiles
+            .get_or_init(|| {
+                let connection = super::dbus::system_connection().ok()?;
+                PowerProfiles::with_connection(connection).ok()
+            })
+            .as_ref()
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn power_supplies(&self) -> Result<Vec<PowerSupply>, Error> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            // Containers and some VMs have no power supply class at all.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(Error::FailedToReadPowerSupplyDir(self.root.clone(), e)),
+        };
+
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths.into_iter().map(PowerSupply::open).collect())
+    }
+
+    pub fn status(&self) -> Result<Status, crate::Error> {
+        let supplies = self
+            .power_supplies()?
+            .into_iter()
+            .filter(|supply| !supply.is_peripheral())
+            .collect::<Vec<_>>();
+
+        let batteries = supplies
+            .iter()
+            .filter(|supply| supply.kind == PowerSupplyType::Battery)
+            .map(PowerSupply::battery_info)
+            .collect::<Vec<_>>();
+        let power_sources = supplies
+            .iter()
+            .filter(|supply| supply.is_adapter())
+            .map(PowerSupply::power_source)
+            .collect::<Vec<_>>();
+        let adapters_online = power_sources
+            .iter()
+            .filter_map(|source| source.online)
+            .collect::<Vec<_>>();
+
+        let power_state = if adapters_online.iter().any(|online| *online) {
+            PowerState::AC
+        } else if !adapters_online.is_empty() {
+            PowerState::Battery
+        } else if batteries.is_empty() {
+            // For desktops without any power supply entries, power state should be treated
+            // as always plugged in.
+            PowerState::AC
+        } else if batteries
+            .iter()
+            .any(|battery| battery.state == BatteryState::Discharging)
+        {
+            PowerState::Battery
+        } else if batteries.iter().any(|battery| {
+            matches!(
+                battery.state,
+                BatteryState::Charging
+                    | BatteryState::Full
+                    | BatteryState::NotCharging
+                    | BatteryState::PendingCharge
+            )
+        }) {
+            PowerState::AC
+        } else {
+            PowerState::Unknown
+        };
+
+        Ok(Status {
+            power_state,
+            // sysfs only reports individual batteries.
+            estimated_energy_percentage: combined_energy_percentage(&batteries),
+            estimated_time_remaining: combined_time_remaining(&batteries),
+            os_time_remaining: None,
+            computed_time_remaining: None,
+            batteries,
+            power_sources,
+            power_saving_mode: false,
+        })
+    }
+
+    /// Batteries of peripheral devices, which are excluded from [`Sysfs::status`].
+    pub fn peripheral_batteries(&self) -> Result<Vec<PeripheralBattery>, crate::Error> {
+        Ok(self
+            .power_supplies()?
+            .iter()
+            .filter(|supply| supply.kind == PowerSupplyType::Battery && supply.is_peripheral())
+            .map(PowerSupply::peripheral_battery)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EstimatedTimeRemaining;
+
+    fn write_supply(root: &Path, name: &str, attrs: &[(&str, &str)]) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (attr, value) in attrs {
+            fs::write(dir.join(attr), format!("{value}\n")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse_battery_state_and_technology() {
+        assert_eq!(parse_battery_state("Charging"), BatteryState::Charging);
+        assert_eq!(
+            parse_battery_state("Not charging"),
+            BatteryState::NotCharging
+        );
+        assert_eq!(parse_battery_state("Unknown"), BatteryState::Unknown);
+        assert_eq!(
+            parse_technology("Li-poly"),
+            BatteryTechnology::LithiumPolymer
+        );
+        assert_eq!(
+            parse_technology("LiMn"),
+            BatteryTechnology::LithiumManganese
+        );
+        assert_eq!(parse_technology("Unknown"), BatteryTechnology::Unknown);
+    }
+
+    #[test]
+    fn test_missing_root_is_treated_as_ac() {
+        let dir = tempfile::tempdir().unwrap();
+        let status = Sysfs::new(dir.path().join("missing")).status().unwrap();
+        assert!(matches!(status.power_state, PowerState::AC));
+        assert!(status.batteries.is_empty());
+        assert_eq!(status.estimated_energy_percentage, None);
+    }
+
+    #[test]
+    fn test_energy_battery_discharging() {
+        let dir = tempfile::tempdir().unwrap();
+        write_supply(dir.path(), "AC", &[("type", "Mains"), ("online", "0")]);
+        write_supply(
+            dir.path(),
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("technology", "Li-ion"),
+                ("energy_now", "25000000"),
+                ("energy_full", "50000000"),
+                ("energy_full_design", "62500000"),
+                ("power_now", "10000000"),
+                ("voltage_now", "12000000"),
+                ("temp", "300"),
+                ("cycle_count", "42"),
+                ("manufacturer", "ACME"),
+            ],
+        );
+
+        let status = Sysfs::new(dir.path()).status().unwrap();
+        assert!(matches!(status.power_state, PowerState::Battery));
+        assert_eq!(status.estimated_energy_percentage, Some(50));
+        assert!(matches!(
+            status.estimated_time_remaining,
+            Some(EstimatedTimeRemaining::Discharging(d)) if d == Duration::from_secs(9000)
+        ));
+
+        let battery = &status.batteries[0];
+        assert_eq!(battery.id, BatteryId::new("BAT0"));
+        assert_eq!(battery.state, BatteryState::Discharging);
+        assert_eq!(battery.technology, BatteryTechnology::LithiumIon);
+        assert_eq!(battery.energy, Some(Energy::from_watt_hours(25.0)));
+        assert_eq!(battery.energy_rate, Some(Power::from_watts(10.0)));
+        assert_eq!(battery.voltage, Some(Voltage::from_volts(12.0)));
+        assert_eq!(battery.state_of_health, Some(0.8));
+        assert!((battery.temperature.unwrap().celsius() - 30.0).abs() < 0.01);
+        assert_eq!(battery.cycle_count, Some(42));
+        assert_eq!(battery.vendor.as_deref(), Some("ACME"));
+        assert_eq!(battery.time_to_empty, Some(Duration::from_secs(9000)));
+    }
+
+    #[test]
+    fn test_charge_battery_charging() {
+        let dir = tempfile::tempdir().unwrap();
+        write_supply(dir.path(), "ADP1", &[("type", "Mains"), ("online", "1")]);
+        write_supply(
+            dir.path(),
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Charging"),
+                ("charge_now", "3000000"),
+                ("charge_full", "4000000"),
+                ("current_now", "2000000"),
+                ("voltage_min_design", "10000000"),
+                ("voltage_now", "10000000"),
+            ],
+        );
+
+        let status = Sysfs::new(dir.path()).status().unwrap();
+        assert!(matches!(status.power_state, PowerState::AC));
+        assert_eq!(status.estimated_energy_percentage, Some(75));
+        assert!(matches!(
+            status.estimated_time_remaining,
+            Some(EstimatedTimeRemaining::Charging(d)) if d == Duration::from_secs(1800)
+        ));
+        let battery = &status.batteries[0];
+        assert_eq!(battery.energy, Some(Energy::from_watt_hours(30.0)));
+        assert_eq!(battery.energy_full, Some(Energy::from_watt_hours(40.0)));
+        assert_eq!(battery.energy_rate, Some(Power::from_watts(20.0)));
+        // Unknown values are not reported as zero.
+        assert_eq!(battery.energy_full_design, None);
+        assert_eq!(battery.temperature, None);
+        assert_eq!(battery.cycle_count, None);
+    }
+
+    #[test]
+    fn test_power_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        write_supply(dir.path(), "ADP1", &[("type", "Mains"), ("online", "0")]);
+        write_supply(
+            dir.path(),
+            "ucsi-source-psy-USBC000:001",
+            &[
+                ("type", "USB"),
+                ("usb_type", "C [PD] PD_PPS"),
+                ("online", "1"),
+                ("voltage_max", "20000000"),
+                ("current_max", "5000000"),
+            ],
+        );
+        write_supply(dir.path(), "usb", &[("type", "USB_DCP"), ("online", "0")]);
+        write_supply(
+            dir.path(),
+            "BAT0",
+            &[("type", "Battery"), ("capacity", "50")],
+        );
+
+        let status = Sysfs::new(dir.path()).status().unwrap();
+        assert!(matches!(status.power_state, PowerState::AC));
+        assert_eq!(status.batteries.len(), 1);
+        let sources = &status.power_sources;
+        assert_eq!(sources.len(), 3);
+
+        assert_eq!(sources[0].name, "ADP1");
+        assert_eq!(sources[0].kind, PowerSourceKind::Mains);
+        assert_eq!(sources[0].online, Some(false));
+        assert_eq!(sources[0].power_max(), None);
+
+        assert_eq!(sources[1].kind, PowerSourceKind::UsbPd);
+        assert_eq!(sources[1].online, Some(true));
+        assert_eq!(sources[1].voltage_max, Some(20.0));
+        assert_eq!(sources[1].current_max, Some(5.0));
+        assert_eq!(sources[1].power_max(), Some(100.0));
+
+        assert_eq!(sources[2].kind, PowerSourceKind::Usb);
+    }
+
+    #[test]
+    fn test_peripheral_batteries() {
+        let dir = tempfile::tempdir().unwrap();
+        write_supply(
+            dir.path(),
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("scope", "System"),
+                ("status", "Discharging"),
+                ("capacity", "90"),
+            ],
+        );
+        write_supply(
+            dir.path(),
+            "hid-00:11:22:33:44:55-battery",
+            &[
+                ("type", "Battery"),
+                ("scope", "Device"),
+                ("status", "Discharging"),
+                ("capacity", "5"),
+                ("model_name", "Wireless Mouse"),
+            ],
+        );
+
+        let sysfs = Sysfs::new(dir.path());
+        let status = sysfs.status().unwrap();
+        assert_eq!(status.batteries.len(), 1);
+        assert_eq!(status.estimated_energy_percentage, Some(90));
+
+        let peripherals = sysfs.peripheral_batteries().unwrap();
+        assert_eq!(
+            peripherals,
+            vec![PeripheralBattery {
+                name: "hid-00:11:22:33:44:55-battery".to_string(),
+                model: Some("Wireless Mouse".to_string()),
+                kind: Default::default(),
+                percentage: Some(5),
+                state: BatteryState::Discharging,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_batteries_without_adapter() {
+        let dir = tempfile::tempdir().unwrap();
+        write_supply(
+            dir.path(),
+            "BAT0",
+            &[("type", "Battery"), ("status", "Full"), ("capacity", "100")],
+        );
+        write_supply(
+            dir.path(),
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Unknown"),
+                ("capacity", "60"),
+            ],
+        );
+
+        let status = Sysfs::new(dir.path()).status().unwrap();
+        assert!(matches!(status.power_state, PowerState::AC));
+        assert_eq!(status.batteries.len(), 2);
+        assert_eq!(status.estimated_energy_percentage, Some(80));
+        assert!(status.estimated_time_remaining.is_none());
+    }
+}
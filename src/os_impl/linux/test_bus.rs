@@ -0,0 +1,56 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+};
+
+/// A private `dbus-daemon` for tests, killed when dropped.
+pub(crate) struct TestBus {
+    child: Child,
+    address: String,
+    _dir: tempfile::TempDir,
+}
+
+impl TestBus {
+    /// Starts a new bus, or returns `None` if `dbus-daemon` is not available.
+    pub fn start() -> Option<Self> {
+        let dir = tempfile::tempdir().ok()?;
+        let mut child = match Command::new("dbus-daemon")
+            .arg("--session")
+            .arg("--nofork")
+            .arg("--print-address=1")
+            .arg(format!(
+                "--address=unix:path={}",
+                dir.path().join("bus").display()
+            ))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("skipping test, failed to start dbus-daemon: {e}");
+                return None;
+            }
+        };
+
+        let mut address = String::new();
+        let stdout = child.stdout.take()?;
+        BufReader::new(stdout).read_line(&mut address).ok()?;
+        Some(TestBus {
+            child,
+            address: address.trim().to_string(),
+            _dir: dir,
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
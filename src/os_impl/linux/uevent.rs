@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+/// Multicast group of the uevents broadcast by the kernel, as opposed to the ones re-broadcast
+/// by udev.
+const KERNEL_UEVENT_GROUP: u32 = 1;
+const UEVENT_BUFFER_SIZE: usize = 8192;
+
+/// A parsed kernel uevent.
+/// Ref: https://www.kernel.org/doc/html/latest/driver-api/driver-model/devices.html
+#[derive(Debug, Default)]
+pub(super) struct Uevent {
+    pub action: String,
+    pub devpath: String,
+    pub env: HashMap<String, String>,
+}
+
+impl Uevent {
+    /// Parses a `action@devpath\0KEY=VALUE\0...` uevent message.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let mut fields = buf
+            .split(|b| *b == 0)
+            .filter(|field| !field.is_empty())
+            .map(String::from_utf8_lossy);
+        let header = fields.next()?;
+        let (action, devpath) = header.split_once('@')?;
+
+        let env = fields
+            .filter_map(|field| {
+                let (key, value) = field.split_once('=')?;
+                Some((key.to_string(), value.to_string()))
+            })
+            .collect();
+
+        Some(Uevent {
+            action: action.to_string(),
+            devpath: devpath.to_string(),
+            env,
+        })
+    }
+
+    pub fn subsystem(&self) -> Option<&str> {
+        self.env.get("SUBSYSTEM").map(String::as_str)
+    }
+}
+
+/// A `NETLINK_KOBJECT_UEVENT` socket subscribed to kernel uevents.
+pub(super) struct UeventSocket {
+    fd: OwnedFd,
+}
+
+impl UeventSocket {
+    pub fn open() -> io::Result<Self> {
+        unsafe {
+            let fd = libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = OwnedFd::from_raw_fd(fd);
+
+            let mut addr: libc::sockaddr_nl = mem::zeroed();
+            addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            addr.nl_groups = KERNEL_UEVENT_GROUP;
+            let ret = libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            );
+            if ret < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(UeventSocket { fd })
+        }
+    }
+
+    /// Receives the next uevent, or `None` if the message could not be parsed.
+    pub fn recv(&self) -> io::Result<Option<Uevent>> {
+        let mut buf = [0u8; UEVENT_BUFFER_SIZE];
+        let len = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Uevent::parse(&buf[..len as usize]))
+    }
+}
+
+impl AsRawFd for UeventSocket {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uevent() {
+        let msg = b"change@/devices/LNXSYSTM:00/LNXSYBUS:00/ACPI0003:00/power_supply/AC\0\
+            ACTION=change\0\
+            DEVPATH=/devices/LNXSYSTM:00/LNXSYBUS:00/ACPI0003:00/power_supply/AC\0\
+            SUBSYSTEM=power_supply\0\
+            POWER_SUPPLY_NAME=AC\0\
+            POWER_SUPPLY_ONLINE=0\0\
+            SEQNUM=1234\0";
+
+        let uevent = Uevent::parse(msg).unwrap();
+        assert_eq!(uevent.action, "change");
+        assert_eq!(
+            uevent.devpath,
+            "/devices/LNXSYSTM:00/LNXSYBUS:00/ACPI0003:00/power_supply/AC"
+        );
+        assert_eq!(uevent.subsystem(), Some("power_supply"));
+        assert_eq!(
+            uevent.env.get("POWER_SUPPLY_ONLINE").map(String::as_str),
+            Some("0")
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_uevent() {
+        assert!(Uevent::parse(b"").is_none());
+        assert!(Uevent::parse(b"libudev\0").is_none());
+    }
+}
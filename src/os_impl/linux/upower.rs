@@ -0,0 +1,758 @@
+//! Power state from the UPower daemon.
+//! Ref: https://upower.freedesktop.org/docs/
+
+use std::{sync::Arc, time::Duration};
+
+use zbus::{
+    blocking::Connection,
+    zvariant::{ObjectPath, OwnedObjectPath},
+};
+
+use crate::{
+    BatteryId, BatteryInfo, BatteryState, BatteryTechnology, Energy, EstimatedTimeRemaining,
+    OnPowerStateChange, PeripheralBattery, PeripheralKind, Power, PowerSource, PowerSourceKind,
+    PowerState, Status, Temperature, Voltage, WatchOptions,
+};
+
+use super::{
+    Error, Guard, PowerProfiles,
+    dbus::{self, Properties},
+    notify_status,
+};
+
+const UPOWER_SERVICE: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const UPOWER_INTERFACE: &str = "org.freedesktop.UPower";
+const DEVICE_INTERFACE: &str = "org.freedesktop.UPower.Device";
+
+#[zbus::proxy(
+    interface = "org.freedesktop.UPower",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower",
+    gen_async = false
+)]
+trait UPowerManager {
+    fn enumerate_devices(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+    fn get_display_device(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+/// Values of the `Type` property of a UPower device.
+mod device_type {
+    pub const LINE_POWER: u32 = 1;
+    pub const BATTERY: u32 = 2;
+    pub const UPS: u32 = 3;
+    pub const MOUSE: u32 = 5;
+    pub const KEYBOARD: u32 = 6;
+    pub const PHONE: u32 = 8;
+    pub const TABLET: u32 = 10;
+    pub const GAMING_INPUT: u32 = 12;
+    pub const PEN: u32 = 13;
+    pub const TOUCHPAD: u32 = 14;
+    pub const HEADSET: u32 = 17;
+    pub const SPEAKERS: u32 = 18;
+    pub const HEADPHONES: u32 = 19;
+}
+
+/// Values of the `State` property of a UPower device.
+mod device_state {
+    pub const CHARGING: u32 = 1;
+    pub const DISCHARGING: u32 = 2;
+    pub const EMPTY: u32 = 3;
+    pub const FULLY_CHARGED: u32 = 4;
+    pub const PENDING_CHARGE: u32 = 5;
+    pub const PENDING_DISCHARGE: u32 = 6;
+}
+
+/// Battery warning level reported by UPower for the display device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UPowerWarningLevel {
+    Unknown,
+    None,
+    /// Only reported for UPSes.
+    Discharging,
+    Low,
+    Critical,
+    Action,
+}
+
+impl From<u32> for UPowerWarningLevel {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => UPowerWarningLevel::None,
+            2 => UPowerWarningLevel::Discharging,
+            3 => UPowerWarningLevel::Low,
+            4 => UPowerWarningLevel::Critical,
+            5 => UPowerWarningLevel::Action,
+            _ => UPowerWarningLevel::Unknown,
+        }
+    }
+}
+
+/// A duration in seconds, where `0` means unknown.
+fn seconds(properties: &Properties, name: &str) -> Option<Duration> {
+    properties
+        .get::<i64>(name)
+        .filter(|secs| *secs > 0)
+        .map(|secs| Duration::from_secs(secs as u64))
+}
+
+fn battery_state(state: Option<u32>) -> BatteryState {
+    match state {
+        Some(device_state::CHARGING) => BatteryState::Charging,
+        Some(device_state::DISCHARGING) => BatteryState::Discharging,
+        Some(device_state::EMPTY) => BatteryState::Empty,
+        Some(device_state::FULLY_CHARGED) => BatteryState::Full,
+        Some(device_state::PENDING_CHARGE) => BatteryState::PendingCharge,
+        Some(device_state::PENDING_DISCHARGE) => BatteryState::PendingDischarge,
+        _ => BatteryState::Unknown,
+    }
+}
+
+fn battery_technology(technology: Option<u32>) -> BatteryTechnology {
+    match technology {
+        Some(1) => BatteryTechnology::LithiumIon,
+        Some(2) => BatteryTechnology::LithiumPolymer,
+        Some(3) => BatteryTechnology::LithiumIronPhosphate,
+        Some(4) => BatteryTechnology::LeadAcid,
+        Some(5) => BatteryTechnology::NickelCadmium,
+        Some(6) => BatteryTechnology::NickelMetalHydride,
+        _ => BatteryTechnology::Unknown,
+    }
+}
+
+fn battery_info(properties: &Properties) -> BatteryInfo {
+    let state = battery_state(properties.get("State"));
+    let technology = battery_technology(properties.get("Technology"));
+    let float = |name| properties.get::<f64>(name).map(|value| value as f32);
+    // UPower reports `0` for most unknown values.
+    let positive = |name| float(name).filter(|value| *value > 0.0);
+
+    // The native path is the sysfs name on Linux, e.g. `BAT0`.
+    let id = match properties.string("NativePath") {
+        Some(path) => BatteryId::new(path),
+        None => BatteryId::fallback(
+            properties.string("Vendor").as_deref(),
+            properties.string("Model").as_deref(),
+            properties.string("Serial").as_deref(),
+        ),
+    };
+
+    BatteryInfo {
+        id,
+        state_of_charge: float("Percentage").unwrap_or_default() / 100.0,
+        // Energy is reported in Wh.
+        energy: float("Energy").map(Energy::from_watt_hours),
+        energy_full: positive("EnergyFull").map(Energy::from_watt_hours),
+        energy_full_design: positive("EnergyFullDesign").map(Energy::from_watt_hours),
+        energy_rate: float("EnergyRate").map(|rate| Power::from_watts(rate.abs())),
+        voltage: positive("Voltage").map(Voltage::from_volts),
+        state_of_health: positive("Capacity").map(|capacity| capacity / 100.0),
+        state,
+        technology,
+        // Temperature is reported in degrees Celsius.
+        temperature: float("Temperature")
+            .filter(|temp| *temp != 0.0)
+            .map(Temperature::from_celsius),
+        // `-1` means unknown.
+        cycle_count: properties
+            .get::<i32>("ChargeCycles")
+            .and_then(|count| u32::try_from(count).ok()),
+        vendor: properties.string("Vendor"),
+        model: properties.string("Model"),
+        serial_number: properties.string("Serial"),
+        time_to_full: seconds(properties, "TimeToFull"),
+        time_to_empty: seconds(properties, "TimeToEmpty"),
+    }
+}
+
+fn power_source(properties: &Properties) -> PowerSource {
+    PowerSource {
+        name: properties.string("NativePath").unwrap_or_default(),
+        kind: match properties.get::<u32>("Type") {
+            Some(device_type::LINE_POWER) => PowerSourceKind::Mains,
+            Some(device_type::UPS) => PowerSourceKind::Ups,
+            _ => PowerSourceKind::Unknown,
+        },
+        online: match properties.get::<u32>("Type") {
+            Some(device_type::UPS) => properties
+                .get::<u32>("State")
+                .map(|state| state != device_state::DISCHARGING),
+            _ => properties.get::<bool>("Online"),
+        },
+        voltage_max: None,
+        current_max: None,
+    }
+}
+
+fn peripheral_battery(properties: &Properties) -> PeripheralBattery {
+    PeripheralBattery {
+        name: properties.string("NativePath").unwrap_or_default(),
+        model: properties.string("Model"),
+        kind: match properties.get::<u32>("Type") {
+            Some(device_type::MOUSE) => PeripheralKind::Mouse,
+            Some(device_type::KEYBOARD) => PeripheralKind::Keyboard,
+            Some(device_type::TOUCHPAD) => PeripheralKind::Touchpad,
+            Some(device_type::PEN) => PeripheralKind::Pen,
+            Some(device_type::GAMING_INPUT) => PeripheralKind::Gamepad,
+            Some(device_type::HEADSET | device_type::HEADPHONES) => PeripheralKind::Headset,
+            Some(device_type::SPEAKERS) => PeripheralKind::Speakers,
+            Some(device_type::PHONE) => PeripheralKind::Phone,
+            Some(device_type::TABLET) => PeripheralKind::Tablet,
+            _ => PeripheralKind::Unknown,
+        },
+        percentage: properties
+            .get::<f64>("Percentage")
+            .map(|percentage| percentage.clamp(0.0, 100.0).round() as u8),
+        state: battery_state(properties.get("State")),
+    }
+}
+
+/// Reads power state from the `org.freedesktop.UPower` service.
+///
+/// UPower aggregates all batteries of the system into a display device, which is used for the
+/// estimated percentage and time remaining.
+#[derive(Clone)]
+pub struct UPower {
+    connection: Connection,
+    profiles: PowerProfiles,
+}
+
+impl UPower {
+    /// Connects to UPower on the system bus.
+    pub fn system() -> Result<Self, crate::Error> {
+        Self::with_connection(dbus::system_connection()?)
+    }
+
+    /// Connects to UPower on the bus at the given D-Bus address.
+    pub fn with_address(address: &str) -> Result<Self, crate::Error> {
+        Self::with_connection(dbus::connect(address)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, crate::Error> {
+        // Power profiles are served on the same bus as UPower.
+        let profiles = PowerProfiles::with_connection(connection.clone())?;
+        Ok(Self {
+            connection,
+            profiles,
+        })
+    }
+
+    fn properties(&self, path: ObjectPath<'_>, interface: &str) -> Result<Properties, Error> {
+        Properties::get_all(&self.connection, UPOWER_SERVICE, path, interface)
+    }
+
+    fn manager(&self) -> Result<UPowerManagerProxy<'_>, Error> {
+        Ok(UPowerManagerProxy::builder(&self.connection)
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()?)
+    }
+
+    fn display_device(&self) -> Result<Properties, Error> {
+        let path = self.manager()?.get_display_device()?;
+        self.properties(path.as_ref(), DEVICE_INTERFACE)
+    }
+
+    /// Properties of all devices, excluding the display device.
+    fn devices(&self) -> Result<Vec<Properties>, Error> {
+        self.manager()?
+            .enumerate_devices()?
+            .iter()
+            .map(|path| self.properties(path.as_ref(), DEVICE_INTERFACE))
+            .collect()
+    }
+
+    pub fn status(&self) -> Result<Status, crate::Error> {
+        let daemon = self.properties(
+            ObjectPath::from_static_str_unchecked(UPOWER_PATH),
+            UPOWER_INTERFACE,
+        )?;
+        let display_device = self.display_device()?;
+        let devices = self.devices()?;
+        let batteries = devices
+            .iter()
+            .filter(|device| {
+                device.get::<u32>("Type") == Some(device_type::BATTERY)
+                    && device.get::<bool>("PowerSupply").unwrap_or(true)
+            })
+            .map(battery_info)
+            .collect();
+        let power_sources = devices
+            .iter()
+            .filter(|device| {
+                matches!(
+                    device.get::<u32>("Type"),
+                    Some(device_type::LINE_POWER | device_type::UPS)
+                )
+            })
+            .map(power_source)
+            .collect();
+
+        let power_state = match daemon.get::<bool>("OnBattery") {
+            Some(true) => PowerState::Battery,
+            Some(false) => PowerState::AC,
+            None => PowerState::Unknown,
+        };
+
+        let is_present = display_device.get::<bool>("IsPresent").unwrap_or(false);
+        let estimated_energy_percentage = display_device
+            .get::<f64>("Percentage")
+            .filter(|_| is_present)
+            .map(|percentage| percentage.clamp(0.0, 100.0).round() as u8);
+        let estimated_time_remaining = match display_device.get::<u32>("State") {
+            Some(device_state::CHARGING) => {
+                seconds(&display_device, "TimeToFull").map(EstimatedTimeRemaining::Charging)
+            }
+            Some(device_state::DISCHARGING) => {
+                seconds(&display_device, "TimeToEmpty").map(EstimatedTimeRemaining::Discharging)
+            }
+            _ => None,
+        };
+        let power_saving_mode = self.profiles.is_power_saver();
+
+        let mut status = Status {
+            power_state,
+            estimated_energy_percentage,
+            os_time_remaining: estimated_time_remaining.clone(),
+            estimated_time_remaining,
+            computed_time_remaining: None,
+            batteries,
+            power_sources,
+            power_saving_mode,
+        };
+        crate::aggregate::fill_missing(&mut status);
+        Ok(status)
+    }
+
+    /// Warning level of the display device, e.g. when the battery is low.
+    pub fn warning_level(&self) -> Result<UPowerWarningLevel, crate::Error> {
+        let display_device = self.display_device()?;
+        Ok(display_device
+            .get::<u32>("WarningLevel")
+            .map(UPowerWarningLevel::from)
+            .unwrap_or(UPowerWarningLevel::Unknown))
+    }
+
+    /// Batteries of peripheral devices, which are excluded from [`UPower::status`].
+    pub fn peripheral_batteries(&self) -> Result<Vec<PeripheralBattery>, crate::Error> {
+        Ok(self
+            .devices()?
+            .iter()
+            .filter(|device| {
+                !matches!(
+                    device.get::<u32>("Type"),
+                    Some(device_type::LINE_POWER | device_type::UPS) | None
+                ) && !device.get::<bool>("PowerSupply").unwrap_or(true)
+            })
+            .map(peripheral_battery)
+            .collect())
+    }
+
+    /// Invokes the callback whenever the batteries of peripheral devices change.
+    pub fn register_peripheral_battery_change_callback<F>(
+        &self,
+        cb: F,
+    ) -> Result<Guard, crate::Error>
+    where
+        F: Fn(Result<Vec<PeripheralBattery>, crate::Error>) + Send + Sync + 'static,
+    {
+        let upower = self.clone();
+        let mut previous = self.peripheral_batteries().ok();
+        let rule = dbus::properties_changed_rule(UPOWER_PATH)?;
+        let worker = dbus::spawn_signal_worker(
+            "powerstate-linux-upower-peripherals",
+            &self.connection,
+            rule,
+            move |message| match message.map_err(|e| Error::DBus(e).into()) {
+                Ok(_) => match upower.peripheral_batteries() {
+                    // Changes of system batteries are signaled as well.
+                    Ok(batteries) if previous.as_ref() == Some(&batteries) => {}
+                    Ok(batteries) => {
+                        previous = Some(batteries.clone());
+                        notify_status(&cb, Ok(batteries));
+                    }
+                    Err(e) => notify_status(&cb, Err(e)),
+                },
+                Err(e) => notify_status(&cb, Err(e)),
+            },
+        )?;
+        Ok(worker.into())
+    }
+
+    /// Invokes the callback whenever a property of UPower, any of its devices or the active
+    /// power profile changes.
+    pub fn register_power_state_change_callback<F>(&self, cb: F) -> Result<Guard, crate::Error>
+    where
+        F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
+    {
+        self.register_power_state_change_callback_with(WatchOptions::default(), cb)
+    }
+
+    /// Like [`UPower::register_power_state_change_callback`], but only for the changes selected
+    /// by `options`.
+    pub fn register_power_state_change_callback_with<F>(
+        &self,
+        options: WatchOptions,
+        cb: F,
+    ) -> Result<Guard, crate::Error>
+    where
+        F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
+    {
+        let cb = crate::watch::filter(&options, || self.status(), cb);
+        let (cb, coalescer) = crate::coalesce::coalesce(options.coalesce, cb)?;
+        let cb: Arc<OnPowerStateChange> = Arc::new(cb);
+        let upower = self.clone();
+        let rule = dbus::properties_changed_rule(UPOWER_PATH)?;
+        let mut workers = vec![dbus::spawn_signal_worker(
+            "powerstate-linux-upower",
+            &self.connection,
+            rule,
+            {
+                let cb = cb.clone();
+                move |message| match message {
+                    Ok(_) => notify_status(cb.as_ref(), upower.status()),
+                    Err(e) => notify_status(cb.as_ref(), Err(Error::DBus(e).into())),
+                }
+            },
+        )?];
+
+        // The legacy power profiles service lives outside of the UPower object tree.
+        if options.saver_mode && !self.profiles.path().starts_with(UPOWER_PATH) {
+            let upower = self.clone();
+            match self
+                .profiles
+                .spawn_profile_worker(move |_| notify_status(cb.as_ref(), upower.status()))
+            {
+                Ok(worker) => workers.push(worker),
+                Err(e) => log::debug!("power profile changes are unavailable: {e}"),
+            }
+        }
+        Ok(Guard::from(workers).with_coalescer(coalescer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use zbus::object_server::SignalEmitter;
+
+    use super::*;
+    use crate::os_impl::linux::test_bus::TestBus;
+
+    struct FakeUPower {
+        on_battery: bool,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.UPower")]
+    impl FakeUPower {
+        fn enumerate_devices(&self) -> Vec<OwnedObjectPath> {
+            vec![
+                ObjectPath::from_static_str_unchecked(
+                    "/org/freedesktop/UPower/devices/line_power_AC",
+                )
+                .into(),
+                ObjectPath::from_static_str_unchecked(
+                    "/org/freedesktop/UPower/devices/battery_BAT0",
+                )
+                .into(),
+                ObjectPath::from_static_str_unchecked("/org/freedesktop/UPower/devices/mouse_0")
+                    .into(),
+            ]
+        }
+
+        fn get_display_device(&self) -> OwnedObjectPath {
+            ObjectPath::from_static_str_unchecked("/org/freedesktop/UPower/devices/DisplayDevice")
+                .into()
+        }
+
+        #[zbus(property)]
+        fn on_battery(&self) -> bool {
+            self.on_battery
+        }
+    }
+
+    struct FakeDevice {
+        kind: u32,
+        power_supply: bool,
+        state: u32,
+        percentage: f64,
+        energy: f64,
+        energy_full: f64,
+        time_to_empty: i64,
+        warning_level: u32,
+    }
+
+    impl FakeDevice {
+        fn line_power() -> Self {
+            FakeDevice {
+                kind: device_type::LINE_POWER,
+                power_supply: true,
+                state: 0,
+                percentage: 0.0,
+                energy: 0.0,
+                energy_full: 0.0,
+                time_to_empty: 0,
+                warning_level: 0,
+            }
+        }
+
+        fn battery() -> Self {
+            FakeDevice {
+                kind: device_type::BATTERY,
+                power_supply: true,
+                state: device_state::DISCHARGING,
+                percentage: 42.0,
+                energy: 21.0,
+                energy_full: 50.0,
+                time_to_empty: 3600,
+                warning_level: 1,
+            }
+        }
+
+        fn mouse() -> Self {
+            FakeDevice {
+                kind: device_type::MOUSE,
+                power_supply: false,
+                state: device_state::DISCHARGING,
+                percentage: 30.0,
+                energy: 0.0,
+                energy_full: 0.0,
+                time_to_empty: 0,
+                warning_level: 1,
+            }
+        }
+    }
+
+    #[zbus::interface(name = "org.freedesktop.UPower.Device")]
+    impl FakeDevice {
+        #[zbus(property, name = "Type")]
+        fn kind(&self) -> u32 {
+            self.kind
+        }
+
+        #[zbus(property)]
+        fn native_path(&self) -> &str {
+            match self.kind {
+                device_type::LINE_POWER => "AC",
+                device_type::MOUSE => "hid-00:11:22:33:44:55-battery",
+                _ => "BAT0",
+            }
+        }
+
+        #[zbus(property)]
+        fn power_supply(&self) -> bool {
+            self.power_supply
+        }
+
+        #[zbus(property)]
+        fn online(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn is_present(&self) -> bool {
+            self.kind == device_type::BATTERY
+        }
+
+        #[zbus(property)]
+        fn state(&self) -> u32 {
+            self.state
+        }
+
+        #[zbus(property)]
+        fn percentage(&self) -> f64 {
+            self.percentage
+        }
+
+        #[zbus(property)]
+        fn energy(&self) -> f64 {
+            self.energy
+        }
+
+        #[zbus(property)]
+        fn energy_full(&self) -> f64 {
+            self.energy_full
+        }
+
+        #[zbus(property)]
+        fn time_to_empty(&self) -> i64 {
+            self.time_to_empty
+        }
+
+        #[zbus(property)]
+        fn time_to_full(&self) -> i64 {
+            0
+        }
+
+        #[zbus(property)]
+        fn technology(&self) -> u32 {
+            1
+        }
+
+        #[zbus(property)]
+        fn vendor(&self) -> &str {
+            "ACME"
+        }
+
+        #[zbus(property)]
+        fn warning_level(&self) -> u32 {
+            self.warning_level
+        }
+    }
+
+    fn serve_fake_upower(bus: &TestBus) -> Connection {
+        zbus::blocking::connection::Builder::address(bus.address())
+            .unwrap()
+            .name(UPOWER_SERVICE)
+            .unwrap()
+            .serve_at(UPOWER_PATH, FakeUPower { on_battery: true })
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/UPower/devices/DisplayDevice",
+                FakeDevice::battery(),
+            )
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/UPower/devices/battery_BAT0",
+                FakeDevice::battery(),
+            )
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/UPower/devices/line_power_AC",
+                FakeDevice::line_power(),
+            )
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/UPower/devices/mouse_0",
+                FakeDevice::mouse(),
+            )
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_battery_state_and_technology() {
+        assert_eq!(battery_state(Some(2)), BatteryState::Discharging);
+        assert_eq!(battery_state(Some(5)), BatteryState::PendingCharge);
+        assert_eq!(battery_state(Some(6)), BatteryState::PendingDischarge);
+        assert_eq!(battery_state(None), BatteryState::Unknown);
+        assert_eq!(
+            battery_technology(Some(3)),
+            BatteryTechnology::LithiumIronPhosphate
+        );
+        assert_eq!(battery_technology(Some(42)), BatteryTechnology::Unknown);
+    }
+
+    #[test]
+    fn test_upower_status() {
+        let Some(bus) = TestBus::start() else {
+            return;
+        };
+        let _service = serve_fake_upower(&bus);
+
+        let upower = UPower::with_address(bus.address()).unwrap();
+        let status = upower.status().unwrap();
+        assert!(matches!(status.power_state, PowerState::Battery));
+        assert_eq!(status.estimated_energy_percentage, Some(42));
+        assert!(matches!(
+            status.estimated_time_remaining,
+            Some(EstimatedTimeRemaining::Discharging(d)) if d == Duration::from_secs(3600)
+        ));
+        assert_eq!(status.os_time_remaining, status.estimated_time_remaining);
+        assert_eq!(status.batteries.len(), 1);
+        let battery = &status.batteries[0];
+        assert_eq!(battery.id, BatteryId::new("BAT0"));
+        assert_eq!(battery.state, BatteryState::Discharging);
+        assert_eq!(battery.technology, BatteryTechnology::LithiumIon);
+        assert_eq!(battery.energy, Some(Energy::from_watt_hours(21.0)));
+        assert_eq!(battery.voltage, None);
+        assert_eq!(battery.vendor.as_deref(), Some("ACME"));
+        assert_eq!(
+            status.power_sources,
+            vec![PowerSource {
+                name: "AC".to_string(),
+                kind: PowerSourceKind::Mains,
+                online: Some(false),
+                ..Default::default()
+            }]
+        );
+        assert_eq!(upower.warning_level().unwrap(), UPowerWarningLevel::None);
+    }
+
+    #[test]
+    fn test_upower_callback() {
+        let Some(bus) = TestBus::start() else {
+            return;
+        };
+        let service = serve_fake_upower(&bus);
+
+        let upower = UPower::with_address(bus.address()).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let guard = upower
+            .register_power_state_change_callback(move |status| {
+                let _ = tx.send(status.map(|status| status.power_state));
+            })
+            .unwrap();
+
+        let iface = service
+            .object_server()
+            .interface::<_, FakeUPower>(UPOWER_PATH)
+            .unwrap();
+        iface.get_mut().on_battery = false;
+        zbus::block_on(async {
+            let emitter = SignalEmitter::new(service.inner(), UPOWER_PATH).unwrap();
+            iface.get().on_battery_changed(&emitter).await.unwrap();
+        });
+
+        let power_state = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert!(matches!(power_state, PowerState::AC));
+        drop(guard);
+    }
+
+    #[test]
+    fn test_upower_peripheral_batteries() {
+        let Some(bus) = TestBus::start() else {
+            return;
+        };
+        let service = serve_fake_upower(&bus);
+
+        let upower = UPower::with_address(bus.address()).unwrap();
+        assert_eq!(upower.status().unwrap().batteries.len(), 1);
+        let mouse = PeripheralBattery {
+            name: "hid-00:11:22:33:44:55-battery".to_string(),
+            model: None,
+            kind: PeripheralKind::Mouse,
+            percentage: Some(30),
+            state: BatteryState::Discharging,
+        };
+        assert_eq!(upower.peripheral_batteries().unwrap(), vec![mouse.clone()]);
+
+        let (tx, rx) = mpsc::channel();
+        let _guard = upower
+            .register_peripheral_battery_change_callback(move |batteries| {
+                let _ = tx.send(batteries);
+            })
+            .unwrap();
+
+        const MOUSE_PATH: &str = "/org/freedesktop/UPower/devices/mouse_0";
+        let iface = service
+            .object_server()
+            .interface::<_, FakeDevice>(MOUSE_PATH)
+            .unwrap();
+        iface.get_mut().percentage = 10.0;
+        zbus::block_on(async {
+            let emitter = SignalEmitter::new(service.inner(), MOUSE_PATH).unwrap();
+            iface.get().percentage_changed(&emitter).await.unwrap();
+        });
+
+        let batteries = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(
+            batteries,
+            vec![PeripheralBattery {
+                percentage: Some(10),
+                ..mouse
+            }]
+        );
+    }
+}
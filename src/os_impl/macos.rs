@@ -1,6 +1,21 @@
-use std::{ffi::c_void, panic, ptr, time::Duration};
+use std::{
+    ffi::c_void,
+    panic, ptr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
 
-use crate::{EstimatedTimeRemaining, PowerState, Status, batteries::get_batteries};
+use crate::{
+    BackendGuard, BatteryId, Capabilities, EstimatedTimeRemaining, OnPowerStateChange,
+    PowerBackend, PowerState, Status, WatchOptions,
+    batteries::{BatteryDevice, assign_device_ids, get_batteries},
+    coalesce::{Coalescer, coalesce},
+    watch,
+};
 
 use objc2::MainThreadMarker;
 use objc2_core_foundation::{
@@ -134,6 +149,7 @@ fn parse_power_source_status(desc: &PowerSourceDictionary) -> Status {
 
     let time_to_full_charge = CFString::from_static_str(PowerSourceDescKey::TIME_TO_FULL_CHARGE);
     let time_to_empty = CFString::from_static_str(PowerSourceDescKey::TIME_TO_EMPTY);
+    // Minutes, -1 while still calculating and 0 for a while after unplugging.
     let mut estimated_time_remaining = None;
     if let Some(time_to_full_charge) = desc.get(time_to_full_charge.as_ref())
         && let Ok(time_to_full_charge) = time_to_full_charge.downcast::<CFNumber>()
@@ -171,22 +187,44 @@ fn parse_power_source_status(desc: &PowerSourceDictionary) -> Status {
     Status {
         power_state,
         estimated_energy_percentage,
+        os_time_remaining: estimated_time_remaining.clone(),
         estimated_time_remaining,
+        computed_time_remaining: None,
         power_saving_mode,
         batteries: vec![],
+        power_sources: vec![],
     }
 }
 
-fn get_power_source_state() -> Result<Status, Error> {
+fn power_source_id(desc: &PowerSourceDictionary) -> Option<i64> {
+    let power_source_id = CFString::from_static_str(PowerSourceDescKey::POWER_SOURCE_ID);
+    desc.get(power_source_id.as_ref())
+        .and_then(|value| value.downcast::<CFNumber>().ok())
+        .and_then(|value| value.as_i64())
+}
+
+fn hardware_serial_number(desc: &PowerSourceDictionary) -> Option<String> {
+    let serial_number = CFString::from_static_str(PowerSourceDescKey::HARDWARE_SERIAL_NUMBER);
+    desc.get(serial_number.as_ref())
+        .and_then(|value| value.downcast::<CFString>().ok())
+        .map(|value| value.to_string())
+}
+
+/// The status of the internal battery, with the internal batteries identified by their power
+/// source ID.
+fn get_power_source_state() -> Result<(Status, Vec<BatteryDevice>), Error> {
     unsafe {
         let blob = IOPSCopyPowerSourcesInfo().ok_or(Error::FailedToCopyPowerSourcesInfo)?;
         let list =
             IOPSCopyPowerSourcesList(Some(&blob)).ok_or(Error::FailedToCopyPowerSourcesList)?;
         let count = list.count();
         if count == 0 {
-            return Ok(status_for_non_battery_device());
+            return Ok((status_for_non_battery_device(), vec![]));
         }
 
+        let mut internal_battery = None;
+        let mut internal_battery_count = 0;
+        let mut devices = vec![];
         let mut fallback_status = None;
         for i in 0..count {
             let ps = list.value_at_index(i as _);
@@ -203,17 +241,36 @@ fn get_power_source_state() -> Result<Status, Error> {
             let status = parse_power_source_status(&desc);
 
             if source_type.as_deref() == Some("InternalBattery") {
-                return Ok(status);
+                internal_battery_count += 1;
+                if let Some(id) = power_source_id(&desc) {
+                    devices.push(BatteryDevice {
+                        id: BatteryId::new(id.to_string()),
+                        serial_number: hardware_serial_number(&desc),
+                        ..Default::default()
+                    });
+                }
+                if internal_battery.is_none() {
+                    internal_battery = Some(status);
+                }
+                continue;
             }
             if fallback_status.is_none() {
                 fallback_status = Some(status);
             }
         }
 
-        if let Some(status) = fallback_status {
-            Ok(status)
+        if let Some(mut status) = internal_battery {
+            // The values of one battery don't apply to all of them, so they are computed from
+            // all batteries instead.
+            if internal_battery_count > 1 {
+                status.estimated_energy_percentage = None;
+                status.estimated_time_remaining = None;
+            }
+            Ok((status, devices))
+        } else if let Some(status) = fallback_status {
+            Ok((status, devices))
         } else {
-            Ok(status_for_non_battery_device())
+            Ok((status_for_non_battery_device(), devices))
         }
     }
 }
@@ -223,6 +280,7 @@ pub struct Guard {
     run_loop: CFRetained<CFRunLoop>,
     source: CFRetained<CFRunLoopSource>,
     context_ptr: *mut Context,
+    _coalescer: Option<Coalescer>,
 }
 
 impl Drop for Guard {
@@ -243,10 +301,13 @@ struct Context {
 }
 
 pub fn get_current_power_state() -> Result<Status, crate::Error> {
-    let mut status = get_power_source_state()?;
-    if let Ok(batteries) = get_batteries() {
+    let (mut status, devices) = get_power_source_state()?;
+    if let Ok(mut batteries) = get_batteries() {
+        assign_device_ids(&mut batteries, devices);
         status.batteries = batteries;
     }
+    // macOS reports no time remaining for a while after being unplugged.
+    crate::aggregate::fill_missing(&mut status);
     Ok(status)
 }
 
@@ -267,9 +328,26 @@ pub fn register_power_state_change_callback<F>(
 where
     F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
 {
-    let context = Box::new(Context {
-        callback: Box::new(cb),
-    });
+    register_power_state_change_callback_with(mtm, WatchOptions::default(), cb)
+}
+
+/// Like [`register_power_state_change_callback`], but only for the changes selected by
+/// `options`.
+///
+/// IOKit reports every power source change through a single notification, so unselected
+/// changes are filtered out after the fact. When coalescing is enabled, the callback runs on a
+/// separate thread instead of the run loop.
+pub fn register_power_state_change_callback_with<F>(
+    mtm: MainThreadMarker,
+    options: WatchOptions,
+    cb: F,
+) -> Result<Guard, crate::Error>
+where
+    F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
+{
+    let cb = watch::filter(&options, get_current_power_state, cb);
+    let (callback, coalescer) = coalesce(options.coalesce, cb)?;
+    let context = Box::new(Context { callback });
     unsafe {
         let run_loop = CFRunLoop::current().ok_or(Error::MissingRunLoop)?;
         let context_ptr = Box::into_raw(context);
@@ -288,6 +366,153 @@ where
             run_loop,
             source,
             context_ptr,
+            _coalescer: coalescer,
         })
     }
 }
+
+/// A run loop that is only used to be stopped from another thread.
+struct RunLoopHandle(CFRetained<CFRunLoop>);
+
+// CFRunLoopStop and CFRunLoopWakeUp may be called from any thread.
+unsafe impl Send for RunLoopHandle {}
+unsafe impl Sync for RunLoopHandle {}
+
+/// Stops the run loop thread of a registered callback when dropped.
+pub struct RunLoopGuard {
+    run_loop: RunLoopHandle,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    _coalescer: Option<Coalescer>,
+}
+
+impl Drop for RunLoopGuard {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.run_loop.0.stop();
+        if let Some(thread) = self.thread.take() {
+            // The guard may be dropped from inside the callback.
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Like [`register_power_state_change_callback_with`], but runs the notification source on the
+/// run loop of a dedicated thread, so it doesn't need the main thread.
+fn register_on_run_loop_thread<F>(
+    options: WatchOptions,
+    cb: F,
+) -> Result<RunLoopGuard, crate::Error>
+where
+    F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
+{
+    let cb = watch::filter(&options, get_current_power_state, cb);
+    let (callback, coalescer) = coalesce(options.coalesce, cb)?;
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    let (tx, rx) = oneshot::channel::<Result<RunLoopHandle, crate::Error>>();
+    let thread = thread::Builder::new()
+        .name("powerstate-macos-loop".to_string())
+        .spawn({
+            let stopped = stopped.clone();
+            move || unsafe {
+                let Some(run_loop) = CFRunLoop::current() else {
+                    let _ = tx.send(Err(Error::MissingRunLoop.into()));
+                    return;
+                };
+                let context_ptr = Box::into_raw(Box::new(Context { callback }));
+                let source = IOPSNotificationCreateRunLoopSource(
+                    Some(on_power_state_change),
+                    context_ptr as *mut c_void,
+                );
+                let Some(source) = source else {
+                    let _ = Box::from_raw(context_ptr);
+                    let _ = tx.send(Err(Error::FailedToCreateRunLoopSource.into()));
+                    return;
+                };
+                run_loop.add_source(Some(&source), kCFRunLoopDefaultMode);
+                let _ = tx.send(Ok(RunLoopHandle(run_loop.clone())));
+
+                // A stop requested before the run loop started running is noticed by the next
+                // iteration.
+                while !stopped.load(Ordering::SeqCst) {
+                    CFRunLoop::run_in_mode(kCFRunLoopDefaultMode, 1.0, false);
+                }
+
+                run_loop.remove_source(Some(&source), kCFRunLoopDefaultMode);
+                let _ = Box::from_raw(context_ptr);
+            }
+        })
+        .map_err(crate::Error::CallbackThreadSpawnFailed)?;
+
+    let run_loop = rx.recv()??;
+    Ok(RunLoopGuard {
+        run_loop,
+        stopped,
+        thread: Some(thread),
+        _coalescer: coalescer,
+    })
+}
+
+// TODO: implement with IORegisterForSystemPower
+pub fn register_sleep_callback<F>(
+    mtm: MainThreadMarker,
+    options: crate::SleepOptions,
+    cb: F,
+) -> Result<Guard, crate::Error>
+where
+    F: Fn(crate::SleepEvent) + Send + Sync + 'static,
+{
+    let _ = (mtm, options, cb);
+    Err(crate::Error::SleepEventsNotSupported)
+}
+
+// TODO: implement with the `Accessory Source` entries of IOPSCopyPowerSourcesInfo
+pub fn get_peripheral_batteries() -> Result<Vec<crate::PeripheralBattery>, crate::Error> {
+    Err(crate::Error::PeripheralBatteriesNotSupported)
+}
+
+pub fn register_peripheral_battery_change_callback<F>(
+    mtm: MainThreadMarker,
+    cb: F,
+) -> Result<Guard, crate::Error>
+where
+    F: Fn(Result<Vec<crate::PeripheralBattery>, crate::Error>) + Send + Sync + 'static,
+{
+    let _ = (mtm, cb);
+    Err(crate::Error::PeripheralBatteriesNotSupported)
+}
+
+pub(crate) const BACKEND_NAMES: &[&str] = &["iokit"];
+
+pub(crate) fn backend(name: &str) -> Option<Result<Box<dyn PowerBackend>, crate::Error>> {
+    (name == "iokit").then(|| Ok(Box::new(IoKitBackend) as _))
+}
+
+/// IOKit power source notifications, delivered on a dedicated run loop thread.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IoKitBackend;
+
+impl PowerBackend for IoKitBackend {
+    fn name(&self) -> &str {
+        "iokit"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::NOTIFICATIONS | Capabilities::TIME_REMAINING | Capabilities::SAVER_MODE
+    }
+
+    fn status(&self) -> Result<Status, crate::Error> {
+        get_current_power_state()
+    }
+
+    fn subscribe(
+        &self,
+        options: WatchOptions,
+        cb: OnPowerStateChange,
+    ) -> Result<BackendGuard, crate::Error> {
+        register_on_run_loop_thread(options, cb).map(BackendGuard::new)
+    }
+}
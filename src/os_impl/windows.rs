@@ -1,13 +1,27 @@
-use std::{panic, time::Duration};
+use std::{ffi::c_void, mem, panic, ptr, time::Duration};
 
 use windows::{
     Win32::{
-        Foundation::{HANDLE, HWND, LPARAM, LRESULT, WPARAM},
+        Devices::DeviceAndDriverInstallation::{
+            DIGCF_DEVICEINTERFACE, DIGCF_PRESENT, GUID_DEVCLASS_BATTERY, HDEVINFO,
+            SP_DEVICE_INTERFACE_DATA, SP_DEVICE_INTERFACE_DETAIL_DATA_W, SP_DEVINFO_DATA,
+            SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInterfaces, SetupDiGetClassDevsW,
+            SetupDiGetDeviceInstanceIdW, SetupDiGetDeviceInterfaceDetailW,
+        },
+        Foundation::{
+            CloseHandle, GENERIC_READ, GENERIC_WRITE, HANDLE, HWND, LPARAM, LRESULT, WPARAM,
+        },
+        Storage::FileSystem::{
+            CreateFileW, FILE_ATTRIBUTE_NORMAL, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+        },
         System::{
+            IO::DeviceIoControl,
             LibraryLoader::GetModuleHandleW,
             Power::{
-                GetSystemPowerStatus, HPOWERNOTIFY, RegisterPowerSettingNotification,
-                SYSTEM_POWER_STATUS,
+                BATTERY_QUERY_INFORMATION, BATTERY_QUERY_INFORMATION_LEVEL, BatteryDeviceName,
+                BatteryManufactureName, BatterySerialNumber, GetSystemPowerStatus, HPOWERNOTIFY,
+                IOCTL_BATTERY_QUERY_INFORMATION, IOCTL_BATTERY_QUERY_TAG,
+                RegisterPowerSettingNotification, SYSTEM_POWER_STATUS,
             },
         },
         UI::WindowsAndMessaging::{
@@ -22,17 +36,23 @@ use windows::{
 };
 
 use crate::{
-    EstimatedTimeRemaining, OnPowerStateChange, PowerState, Status, batteries::get_batteries,
+    BackendGuard, BatteryId, Capabilities, EstimatedTimeRemaining, OnPowerStateChange,
+    PowerBackend, PowerState, Status, WatchOptions,
+    batteries::{BatteryDevice, assign_device_ids, get_batteries},
+    coalesce::{Coalescer, coalesce},
+    watch,
 };
 
 // Ref: https://learn.microsoft.com/en-us/windows/win32/power/power-setting-guids
 const GUID_ACDC_POWER_SOURCE: &str = "5D3E9A59-E9D5-4B00-A6BD-FF34FF516548";
 const GUID_BATTERY_PERCENTAGE_REMAINING: &str = "A7AD8041-B45A-4CAE-87A3-EECBB468A9E1";
+const GUID_POWER_SAVING_STATUS: &str = "E00958C0-C213-4ACE-AC77-FECCED2EEEA5";
 const ERROR_CLASS_ALREADY_EXISTS: u32 = 1410;
 
 pub struct Guard {
     hwnd: HWND,
     tokens: Vec<Owned<HPOWERNOTIFY>>,
+    _coalescer: Option<Coalescer>,
 }
 
 unsafe impl Send for Guard {}
@@ -72,7 +92,10 @@ fn register_window_class(hinst: windows::Win32::Foundation::HMODULE) -> windows:
     Ok(())
 }
 
-fn create_message_only_window(cb: OnPowerStateChange) -> windows::core::Result<Guard> {
+fn create_message_only_window(
+    cb: OnPowerStateChange,
+    guids: &[&str],
+) -> windows::core::Result<Guard> {
     let hinst = unsafe { GetModuleHandleW(None)? };
     register_window_class(hinst)?;
 
@@ -106,52 +129,41 @@ fn create_message_only_window(cb: OnPowerStateChange) -> windows::core::Result<G
 
     let mut tokens = Vec::new();
 
-    // Register AC/DC power source change notification
-    let guid: GUID = match GUID_ACDC_POWER_SOURCE.try_into() {
-        Ok(guid) => guid,
-        Err(e) => {
-            unsafe {
-                let _ = DestroyWindow(hwnd);
-            }
-            return Err(e);
-        }
-    };
-    let token = match unsafe {
-        RegisterPowerSettingNotification(HANDLE(hwnd.0), &guid, DEVICE_NOTIFY_WINDOW_HANDLE)
-    } {
-        Ok(token) => unsafe { Owned::new(token) },
-        Err(e) => {
-            unsafe {
-                let _ = DestroyWindow(hwnd);
-            }
-            return Err(e);
-        }
-    };
-    tokens.push(token);
-    // Register battery percentage change notification
-    let guid: GUID = match GUID_BATTERY_PERCENTAGE_REMAINING.try_into() {
-        Ok(guid) => guid,
-        Err(e) => {
-            unsafe {
-                let _ = DestroyWindow(hwnd);
+    for guid in guids {
+        let guid: GUID = match (*guid).try_into() {
+            Ok(guid) => guid,
+            Err(e) => {
+                unsafe {
+                    let _ = DestroyWindow(hwnd);
+                }
+                return Err(e);
             }
-            return Err(e);
-        }
-    };
-    let token = match unsafe {
-        RegisterPowerSettingNotification(HANDLE(hwnd.0), &guid, DEVICE_NOTIFY_WINDOW_HANDLE)
-    } {
-        Ok(token) => unsafe { Owned::new(token) },
-        Err(e) => {
-            unsafe {
-                let _ = DestroyWindow(hwnd);
+        };
+        let token = match unsafe {
+            RegisterPowerSettingNotification(HANDLE(hwnd.0), &guid, DEVICE_NOTIFY_WINDOW_HANDLE)
+        } {
+            Ok(token) => unsafe { Owned::new(token) },
+            Err(e) => {
+                unsafe {
+                    let _ = DestroyWindow(hwnd);
+                }
+                return Err(e);
             }
-            return Err(e);
-        }
-    };
-    tokens.push(token);
+        };
+        tokens.push(token);
+    }
+
+    Ok(Guard {
+        hwnd,
+        tokens,
+        _coalescer: None,
+    })
+}
 
-    Ok(Guard { hwnd, tokens })
+/// Windows reports `u32::MAX` while it is still calculating and 0 right after unplugging,
+/// neither of which is an estimate.
+fn known_life_time(seconds: u32) -> Option<Duration> {
+    (seconds != 0 && seconds != u32::MAX).then(|| Duration::from_secs(seconds as u64))
 }
 
 /// Get the current power state of the system.
@@ -166,31 +178,194 @@ pub fn get_current_power_state() -> Result<Status, crate::Error> {
     } else {
         Some(power_status.BatteryLifePercent)
     };
-    let estimated_time_remaining = if power_status.BatteryFullLifeTime != u32::MAX {
-        Some(EstimatedTimeRemaining::Charging(Duration::from_secs(
-            power_status.BatteryFullLifeTime as u64,
-        )))
-    } else if power_status.BatteryLifeTime != u32::MAX {
-        Some(EstimatedTimeRemaining::Discharging(Duration::from_secs(
-            power_status.BatteryLifeTime as u64,
-        )))
+    // Windows only estimates the time to empty. `BatteryFullLifeTime` is the battery life at full
+    // charge, not the time until full.
+    let estimated_time_remaining = if power_status.ACLineStatus == 0 {
+        known_life_time(power_status.BatteryLifeTime).map(EstimatedTimeRemaining::Discharging)
     } else {
         None
     };
 
-    let batteries = get_batteries().unwrap_or_default();
+    let mut batteries = get_batteries().unwrap_or_default();
+    match battery_devices() {
+        Ok(devices) => assign_device_ids(&mut batteries, devices),
+        Err(e) => log::debug!("failed to enumerate battery devices: {e}"),
+    }
 
-    Ok(Status {
+    let mut status = Status {
         estimated_energy_percentage,
+        os_time_remaining: estimated_time_remaining.clone(),
         estimated_time_remaining,
+        computed_time_remaining: None,
         batteries,
+        power_sources: vec![],
         power_state: match power_status.ACLineStatus {
             0 => PowerState::Battery,
             1 => PowerState::AC,
             _ => PowerState::Unknown,
         },
         power_saving_mode: power_status.SystemStatusFlag == 1,
-    })
+    };
+    // The time is unknown on AC and while Windows is still calculating it.
+    crate::aggregate::fill_missing(&mut status);
+    Ok(status)
+}
+
+/// The batteries with their device instance path, e.g. `ACPI\PNP0C0A\1`, in the order
+/// starship-battery enumerates them.
+fn battery_devices() -> windows::core::Result<Vec<BatteryDevice>> {
+    let mut devices = vec![];
+    unsafe {
+        let device_info_set = SetupDiGetClassDevsW(
+            Some(&GUID_DEVCLASS_BATTERY),
+            PCWSTR::null(),
+            None,
+            DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
+        )?;
+        for index in 0.. {
+            let mut interface = SP_DEVICE_INTERFACE_DATA {
+                cbSize: mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as u32,
+                ..Default::default()
+            };
+            // Fails with ERROR_NO_MORE_ITEMS after the last battery.
+            if SetupDiEnumDeviceInterfaces(
+                device_info_set,
+                None,
+                &GUID_DEVCLASS_BATTERY,
+                index,
+                &mut interface,
+            )
+            .is_err()
+            {
+                break;
+            }
+            match battery_device(device_info_set, &interface) {
+                Ok(device) => devices.push(device),
+                Err(e) => log::debug!("failed to read battery device {index}: {e}"),
+            }
+        }
+        let _ = SetupDiDestroyDeviceInfoList(device_info_set);
+    }
+    Ok(devices)
+}
+
+unsafe fn battery_device(
+    device_info_set: HDEVINFO,
+    interface: &SP_DEVICE_INTERFACE_DATA,
+) -> windows::core::Result<BatteryDevice> {
+    unsafe {
+        // Only reports the size of the detail data, failing with ERROR_INSUFFICIENT_BUFFER.
+        let mut size = 0;
+        let _ = SetupDiGetDeviceInterfaceDetailW(
+            device_info_set,
+            interface,
+            None,
+            0,
+            Some(&mut size),
+            None,
+        );
+        // Aligned for the detail data, which ends with the variable length device path.
+        let mut buffer = vec![0u64; (size as usize).div_ceil(8).max(1)];
+        let detail = buffer
+            .as_mut_ptr()
+            .cast::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>();
+        (*detail).cbSize = mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() as u32;
+        let mut device_info = SP_DEVINFO_DATA {
+            cbSize: mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+        SetupDiGetDeviceInterfaceDetailW(
+            device_info_set,
+            interface,
+            Some(detail),
+            size,
+            None,
+            Some(&mut device_info),
+        )?;
+
+        let mut instance_id = [0u16; 256];
+        SetupDiGetDeviceInstanceIdW(device_info_set, &device_info, Some(&mut instance_id), None)?;
+
+        let device_path = PCWSTR(ptr::addr_of!((*detail).DevicePath).cast());
+        let handle = CreateFileW(
+            device_path,
+            (GENERIC_READ | GENERIC_WRITE).0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            None,
+        )?;
+        let device = battery_tag(handle).map(|tag| BatteryDevice {
+            id: BatteryId::new(wide_to_string(&instance_id)),
+            vendor: query_battery_string(handle, tag, BatteryManufactureName),
+            model: query_battery_string(handle, tag, BatteryDeviceName),
+            serial_number: query_battery_string(handle, tag, BatterySerialNumber),
+        });
+        let _ = CloseHandle(handle);
+        device
+    }
+}
+
+/// The tag of the battery currently in the slot of `handle`.
+unsafe fn battery_tag(handle: HANDLE) -> windows::core::Result<u32> {
+    let wait: u32 = 0;
+    let mut tag: u32 = 0;
+    let mut returned = 0;
+    unsafe {
+        DeviceIoControl(
+            handle,
+            IOCTL_BATTERY_QUERY_TAG,
+            Some(ptr::addr_of!(wait).cast::<c_void>()),
+            mem::size_of_val(&wait) as u32,
+            Some(ptr::addr_of_mut!(tag).cast::<c_void>()),
+            mem::size_of_val(&tag) as u32,
+            Some(&mut returned),
+            None,
+        )?;
+    }
+    // No battery in the slot.
+    if tag == 0 {
+        return Err(windows::core::Error::from_thread());
+    }
+    Ok(tag)
+}
+
+/// A string of the battery, `None` if it isn't reported.
+unsafe fn query_battery_string(
+    handle: HANDLE,
+    tag: u32,
+    level: BATTERY_QUERY_INFORMATION_LEVEL,
+) -> Option<String> {
+    let query = BATTERY_QUERY_INFORMATION {
+        BatteryTag: tag,
+        InformationLevel: level,
+        ..Default::default()
+    };
+    let mut buffer = [0u16; 256];
+    let mut returned = 0;
+    unsafe {
+        DeviceIoControl(
+            handle,
+            IOCTL_BATTERY_QUERY_INFORMATION,
+            Some(ptr::addr_of!(query).cast::<c_void>()),
+            mem::size_of_val(&query) as u32,
+            Some(buffer.as_mut_ptr().cast::<c_void>()),
+            mem::size_of_val(&buffer) as u32,
+            Some(&mut returned),
+            None,
+        )
+        .ok()?;
+    }
+    let length = (returned as usize / 2).min(buffer.len());
+    let value = wide_to_string(&buffer[..length]);
+    (!value.is_empty()).then_some(value)
+}
+
+/// Decodes a wide string up to its terminating null, if any.
+fn wide_to_string(wide: &[u16]) -> String {
+    let length = wide.iter().position(|c| *c == 0).unwrap_or(wide.len());
+    String::from_utf16_lossy(&wide[..length]).trim().to_string()
 }
 
 extern "system" fn wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
@@ -224,11 +399,38 @@ pub fn register_power_state_change_callback<F>(cb: F) -> Result<Guard, crate::Er
 where
     F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
 {
-    let (tx, rx) = oneshot::channel();
+    register_power_state_change_callback_with(WatchOptions::default(), cb)
+}
+
+/// Like [`register_power_state_change_callback`], but only for the changes selected by
+/// `options`.
+///
+/// Only the power setting notifications needed for the selected changes are registered.
+pub fn register_power_state_change_callback_with<F>(
+    options: WatchOptions,
+    cb: F,
+) -> Result<Guard, crate::Error>
+where
+    F: Fn(Result<Status, crate::Error>) + Send + Sync + 'static,
+{
+    let mut guids = vec![];
+    if options.power_source {
+        guids.push(GUID_ACDC_POWER_SOURCE);
+    }
+    if options.percentage_step.is_some() || options.time_remaining || options.battery_hotplug {
+        guids.push(GUID_BATTERY_PERCENTAGE_REMAINING);
+    }
+    if options.saver_mode {
+        guids.push(GUID_POWER_SAVING_STATUS);
+    }
+
+    let cb = watch::filter(&options, get_current_power_state, cb);
+    let (cb, coalescer) = coalesce(options.coalesce, cb)?;
+    let (tx, rx) = oneshot::channel::<Result<Guard, crate::Error>>();
     std::thread::Builder::new()
         .name("powerstate-windows-loop".to_string())
         .spawn(move || {
-            match create_message_only_window(Box::new(cb)) {
+            match create_message_only_window(cb, &guids) {
                 Ok(guard) => {
                     let _ = tx.send(Ok(guard));
                 }
@@ -254,5 +456,64 @@ where
         })
         .map_err(crate::Error::CallbackThreadSpawnFailed)?;
 
-    rx.recv().map_err(crate::Error::from)?
+    let mut guard = rx.recv().map_err(crate::Error::from)??;
+    guard._coalescer = coalescer;
+    Ok(guard)
+}
+
+// TODO: implement with PBT_APMSUSPEND / PBT_APMRESUMEAUTOMATIC
+pub fn register_sleep_callback<F>(
+    options: crate::SleepOptions,
+    cb: F,
+) -> Result<Guard, crate::Error>
+where
+    F: Fn(crate::SleepEvent) + Send + Sync + 'static,
+{
+    let _ = (options, cb);
+    Err(crate::Error::SleepEventsNotSupported)
+}
+
+// TODO: enumerate Bluetooth and HID devices reporting a battery level
+pub fn get_peripheral_batteries() -> Result<Vec<crate::PeripheralBattery>, crate::Error> {
+    Err(crate::Error::PeripheralBatteriesNotSupported)
+}
+
+pub fn register_peripheral_battery_change_callback<F>(cb: F) -> Result<Guard, crate::Error>
+where
+    F: Fn(Result<Vec<crate::PeripheralBattery>, crate::Error>) + Send + Sync + 'static,
+{
+    let _ = cb;
+    Err(crate::Error::PeripheralBatteriesNotSupported)
+}
+
+pub(crate) const BACKEND_NAMES: &[&str] = &["windows"];
+
+pub(crate) fn backend(name: &str) -> Option<Result<Box<dyn PowerBackend>, crate::Error>> {
+    (name == "windows").then(|| Ok(Box::new(WindowsBackend) as _))
+}
+
+/// Power setting notifications of a message-only window.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WindowsBackend;
+
+impl PowerBackend for WindowsBackend {
+    fn name(&self) -> &str {
+        "windows"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::NOTIFICATIONS | Capabilities::TIME_REMAINING | Capabilities::SAVER_MODE
+    }
+
+    fn status(&self) -> Result<Status, crate::Error> {
+        get_current_power_state()
+    }
+
+    fn subscribe(
+        &self,
+        options: WatchOptions,
+        cb: OnPowerStateChange,
+    ) -> Result<BackendGuard, crate::Error> {
+        register_power_state_change_callback_with(options, cb).map(BackendGuard::new)
+    }
 }
@@ -0,0 +1,174 @@
+use std::{
+    sync::mpsc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{ChangeTracker, Error, Status, get_current_power_state};
+
+/// Interval used when falling back to polling because OS notifications are unavailable.
+pub const DEFAULT_POLLING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Stops the polling thread when dropped.
+pub struct PollingGuard {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for PollingGuard {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            // The guard may be dropped from inside the callback.
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+pub(crate) fn spawn_poller<S, F>(
+    interval: Duration,
+    mut source: S,
+    cb: F,
+) -> Result<PollingGuard, Error>
+where
+    S: FnMut() -> Result<Status, Error> + Send + 'static,
+    F: Fn(Result<Status, Error>) + Send + Sync + 'static,
+{
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    let thread = thread::Builder::new()
+        .name("powerstate-polling".to_string())
+        .spawn(move || {
+            let mut tracker: Option<ChangeTracker> = None;
+            let mut failing = false;
+            loop {
+                let notify = match source() {
+                    Ok(status) if failing => {
+                        failing = false;
+                        tracker = Some(ChangeTracker::new(status.clone()));
+                        Some(Ok(status))
+                    }
+                    Ok(status) => match &mut tracker {
+                        Some(tracker) => tracker.update(status).map(|change| Ok(change.current)),
+                        // Nothing to compare the first status with.
+                        None => {
+                            tracker = Some(ChangeTracker::new(status));
+                            None
+                        }
+                    },
+                    // Only report the first of consecutive errors.
+                    Err(e) => (!std::mem::replace(&mut failing, true)).then_some(Err(e)),
+                };
+                if let Some(status) = notify {
+                    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| cb(status)));
+                }
+
+                match stop_rx.recv_timeout(interval) {
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+            }
+        })
+        .map_err(Error::CallbackThreadSpawnFailed)?;
+
+    Ok(PollingGuard {
+        stop: Some(stop_tx),
+        thread: Some(thread),
+    })
+}
+
+/// Polls [`get_current_power_state`] every `interval` and invokes the callback when the status
+/// changed.
+///
+/// This works in environments where OS notifications are unavailable, e.g. containers and
+/// sandboxes, at the cost of latency. The first status is not reported.
+pub fn register_polling_callback<F>(interval: Duration, cb: F) -> Result<PollingGuard, Error>
+where
+    F: Fn(Result<Status, Error>) + Send + Sync + 'static,
+{
+    spawn_poller(interval, get_current_power_state, cb)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, sync::Arc, sync::Mutex};
+
+    use super::*;
+    use crate::{
+        PowerState,
+        test_util::{query_failed, status},
+    };
+
+    #[test]
+    fn test_poller_reports_changes_only() {
+        let script = Arc::new(Mutex::new(VecDeque::from([
+            Ok(status(PowerState::AC, 50)),
+            Ok(status(PowerState::AC, 50)),
+            Ok(status(PowerState::Battery, 50)),
+            Err(query_failed()),
+            Err(query_failed()),
+            Ok(status(PowerState::Battery, 49)),
+        ])));
+        let last = status(PowerState::Battery, 49);
+
+        let (tx, rx) = mpsc::channel();
+        let _guard = spawn_poller(
+            Duration::from_millis(1),
+            move || {
+                script
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .unwrap_or(Ok(last.clone()))
+            },
+            move |status| {
+                let _ = tx.send(status.map(|s| (s.power_state, s.estimated_energy_percentage)));
+            },
+        )
+        .unwrap();
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            rx.recv_timeout(timeout).unwrap().unwrap(),
+            (PowerState::Battery, Some(50))
+        );
+        assert!(rx.recv_timeout(timeout).unwrap().is_err());
+        assert_eq!(
+            rx.recv_timeout(timeout).unwrap().unwrap(),
+            (PowerState::Battery, Some(49))
+        );
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+
+    #[test]
+    fn test_poller_reports_slow_drift() {
+        let discharging = |secs| Status {
+            estimated_time_remaining: Some(crate::EstimatedTimeRemaining::Discharging(
+                Duration::from_secs(secs),
+            )),
+            ..status(PowerState::Battery, 50)
+        };
+        let script = Arc::new(Mutex::new(VecDeque::from([3600, 3570, 3540])));
+
+        let (tx, rx) = mpsc::channel();
+        let _guard = spawn_poller(
+            Duration::from_millis(1),
+            move || {
+                Ok(discharging(
+                    script.lock().unwrap().pop_front().unwrap_or(3540),
+                ))
+            },
+            move |status| {
+                let _ = tx.send(status.map(|s| s.estimated_time_remaining));
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap(),
+            discharging(3540).estimated_time_remaining
+        );
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+}
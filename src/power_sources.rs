@@ -0,0 +1,45 @@
+/// Kind of an external power source.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum PowerSourceKind {
+    Mains,
+    #[strum(serialize = "USB")]
+    Usb,
+    /// USB Power Delivery.
+    #[strum(serialize = "USB_PD")]
+    UsbPd,
+    /// USB Type-C without Power Delivery.
+    #[strum(serialize = "USB_C")]
+    UsbC,
+    Wireless,
+    #[strum(serialize = "UPS")]
+    Ups,
+    #[default]
+    Unknown,
+}
+
+/// An external power source, e.g. an AC adapter or a USB charger.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PowerSource {
+    /// Platform name of the source, e.g. `ADP1` on Linux.
+    pub name: String,
+    pub kind: PowerSourceKind,
+    /// Whether the source is connected and supplying power, if known.
+    pub online: Option<bool>,
+    /// Maximum voltage in volts.
+    pub voltage_max: Option<f32>,
+    /// Maximum current in amperes.
+    pub current_max: Option<f32>,
+}
+
+impl PowerSource {
+    /// Maximum power in watts, when both voltage and current are known.
+    pub fn power_max(&self) -> Option<f32> {
+        Some(self.voltage_max? * self.current_max?)
+    }
+}
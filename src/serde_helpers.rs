@@ -0,0 +1,143 @@
+//! Stable encodings for fields whose types don't have one of their own.
+
+/// Encodes a [`std::time::Duration`] as a number of seconds.
+pub(crate) mod duration_secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(D::Error::custom)
+    }
+}
+
+/// Encodes an optional [`std::time::Duration`] as a number of seconds or `null`.
+pub(crate) mod option_duration_secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&duration.as_secs_f64()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?
+            .map(|secs| Duration::try_from_secs_f64(secs).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::{
+        BatteryInfo, BatteryState, BatteryTechnology, Energy, EstimatedTimeRemaining, PowerSource,
+        PowerSourceKind, PowerState, Status, TimeEstimate,
+    };
+
+    fn status() -> Status {
+        Status {
+            power_state: PowerState::Battery,
+            estimated_energy_percentage: Some(42),
+            estimated_time_remaining: Some(EstimatedTimeRemaining::Discharging(
+                Duration::from_secs(5400),
+            )),
+            os_time_remaining: None,
+            computed_time_remaining: Some(TimeEstimate {
+                time_remaining: EstimatedTimeRemaining::Discharging(Duration::from_secs(6000)),
+                confidence: 0.75,
+            }),
+            batteries: vec![BatteryInfo {
+                state_of_charge: 0.42,
+                energy: Some(Energy::from_watt_hours(25.0)),
+                state: BatteryState::Discharging,
+                technology: BatteryTechnology::LithiumPolymer,
+                cycle_count: Some(123),
+                model: Some("Model".to_string()),
+                time_to_empty: Some(Duration::from_secs(5400)),
+                ..Default::default()
+            }],
+            power_sources: vec![PowerSource {
+                name: "ucsi-source-psy-USBC000:001".to_string(),
+                kind: PowerSourceKind::UsbPd,
+                online: Some(false),
+                ..Default::default()
+            }],
+            power_saving_mode: true,
+        }
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        let status = status();
+        let value = serde_json::to_value(&status).unwrap();
+        assert_eq!(value["power_state"], json!("battery"));
+        assert_eq!(
+            value["estimated_time_remaining"],
+            json!({ "discharging": 5400.0 })
+        );
+        assert_eq!(
+            value["computed_time_remaining"],
+            json!({ "time_remaining": { "discharging": 6000.0 }, "confidence": 0.75 })
+        );
+        assert_eq!(value["batteries"][0]["state"], json!("discharging"));
+        assert_eq!(
+            value["batteries"][0]["technology"],
+            json!("lithium-polymer")
+        );
+        assert_eq!(value["power_sources"][0]["kind"], json!("usb_pd"));
+
+        let decoded: Status = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(format!("{decoded:?}"), format!("{status:?}"));
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), value);
+    }
+
+    #[test]
+    fn test_decode_compatible() {
+        let status: Status = serde_json::from_value(json!({
+            "power_state": "ac",
+            "estimated_time_remaining": { "charging": 90.5 },
+            "batteries": [
+                { "state": "full", "technology": "solid-state" },
+                { "state": "balancing" },
+            ],
+        }))
+        .unwrap();
+        assert_eq!(status.power_state, PowerState::AC);
+        assert_eq!(
+            status.estimated_time_remaining,
+            Some(EstimatedTimeRemaining::Charging(Duration::from_millis(
+                90_500
+            )))
+        );
+        assert_eq!(status.batteries[0].state, BatteryState::Full);
+        assert_eq!(status.batteries[0].technology, BatteryTechnology::Unknown);
+        assert_eq!(status.batteries[1].state, BatteryState::Unknown);
+        assert!(status.power_sources.is_empty());
+
+        assert!(serde_json::from_value::<Status>(json!({ "power_state": "solar" })).is_err());
+        assert!(
+            serde_json::from_value::<Status>(json!({
+                "estimated_time_remaining": { "charging": -1.0 }
+            }))
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use futures_core::Stream;
+
+use crate::{Error, Guard, OnPowerStateChange, Status};
+
+#[derive(Default)]
+struct Shared {
+    queue: VecDeque<Result<Status, Error>>,
+    waker: Option<Waker>,
+}
+
+/// A stream of power state changes, unregistering its callback when dropped.
+///
+/// Changes are queued until polled, so none are lost while the consumer is busy.
+pub struct PowerStateStream<G = Guard> {
+    // Dropped first, so that the callback stops before the queue goes away.
+    _guard: G,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl<G> PowerStateStream<G> {
+    /// Streams the changes delivered to the callback passed to `register`, which returns the
+    /// guard of the registration, e.g. [`crate::register_power_state_change_callback`].
+    pub fn with_registration<R>(register: R) -> Result<Self, Error>
+    where
+        R: FnOnce(OnPowerStateChange) -> Result<G, Error>,
+    {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let guard = register(Box::new({
+            let shared = shared.clone();
+            move |status| {
+                let mut shared = shared.lock().unwrap_or_else(|e| e.into_inner());
+                shared.queue.push_back(status);
+                if let Some(waker) = shared.waker.take() {
+                    waker.wake();
+                }
+            }
+        }))?;
+        Ok(PowerStateStream {
+            _guard: guard,
+            shared,
+        })
+    }
+}
+
+impl<G> Stream for PowerStateStream<G> {
+    type Item = Result<Status, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match shared.queue.pop_front() {
+            Some(status) => Poll::Ready(Some(status)),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<G> Unpin for PowerStateStream<G> {}
+
+/// Returns a stream of power state changes of the system.
+#[cfg(not(target_os = "macos"))]
+pub fn power_state_stream() -> Result<PowerStateStream, Error> {
+    PowerStateStream::with_registration(crate::register_power_state_change_callback)
+}
+
+/// Returns a stream of power state changes of the system.
+#[cfg(target_os = "macos")]
+pub fn power_state_stream(mtm: objc2::MainThreadMarker) -> Result<PowerStateStream, Error> {
+    PowerStateStream::with_registration(|cb| crate::register_power_state_change_callback(mtm, cb))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::{PowerState, test_util::query_failed};
+
+    struct FakeGuard(Arc<AtomicBool>);
+
+    impl Drop for FakeGuard {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn poll_next<G>(stream: &mut PowerStateStream<G>) -> Poll<Option<Result<Status, Error>>> {
+        Pin::new(stream).poll_next(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn test_stream_delivers_changes_and_unregisters() {
+        let callback = Arc::new(Mutex::new(None));
+        let dropped = Arc::new(AtomicBool::new(false));
+        let mut stream = PowerStateStream::with_registration(|cb| {
+            *callback.lock().unwrap() = Some(cb);
+            Ok(FakeGuard(dropped.clone()))
+        })
+        .unwrap();
+        let notify = |status| (callback.lock().unwrap().as_ref().unwrap())(status);
+
+        assert!(poll_next(&mut stream).is_pending());
+        notify(Ok(Status {
+            power_state: PowerState::Battery,
+            ..Default::default()
+        }));
+        notify(Err(query_failed()));
+
+        let Poll::Ready(Some(Ok(status))) = poll_next(&mut stream) else {
+            panic!("expected a status");
+        };
+        assert_eq!(status.power_state, PowerState::Battery);
+        assert!(matches!(
+            poll_next(&mut stream),
+            Poll::Ready(Some(Err(Error::CallbackThreadSpawnFailed(_))))
+        ));
+        assert!(poll_next(&mut stream).is_pending());
+
+        drop(stream);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_registration_error() {
+        let result = PowerStateStream::<()>::with_registration(|_| Err(query_failed()));
+        assert!(result.is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_power_state_stream() {
+        let stream = power_state_stream().unwrap();
+        drop(stream);
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{Error, Guard, OnPowerStateChange, Status};
+
+/// What to do when a change arrives and the queue of a [`Subscription`] is full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest queued change to make room for the new one.
+    #[default]
+    DropOldest,
+    /// Only keep the latest change, replacing whatever is queued.
+    KeepLatest,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscribeOptions {
+    /// Maximum number of queued changes, at least 1.
+    pub capacity: usize,
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Default for SubscribeOptions {
+    fn default() -> Self {
+        SubscribeOptions {
+            capacity: 16,
+            overflow_policy: OverflowPolicy::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    items: VecDeque<Result<Status, Error>>,
+    dropped: u64,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Receives power state changes on any thread, unregistering its callback when dropped.
+///
+/// The registered callback only queues changes, so it never blocks the OS notification
+/// thread, however slow the receiver is.
+pub struct Subscription<G = Guard> {
+    // Dropped first, so that the callback stops before the queue goes away.
+    _guard: G,
+    shared: Arc<Shared>,
+}
+
+impl<G> Subscription<G> {
+    /// Queues the changes delivered to the callback passed to `register`, which returns the
+    /// guard of the registration, e.g. [`crate::register_power_state_change_callback`].
+    pub fn with_registration<R>(options: SubscribeOptions, register: R) -> Result<Self, Error>
+    where
+        R: FnOnce(OnPowerStateChange) -> Result<G, Error>,
+    {
+        let shared = Arc::new(Shared {
+            queue: Mutex::default(),
+            ready: Condvar::new(),
+        });
+        let capacity = match options.overflow_policy {
+            OverflowPolicy::DropOldest => options.capacity.max(1),
+            OverflowPolicy::KeepLatest => 1,
+        };
+        let guard = register(Box::new({
+            let shared = shared.clone();
+            move |status| {
+                let mut queue = shared.lock();
+                while queue.items.len() >= capacity {
+                    queue.items.pop_front();
+                    queue.dropped += 1;
+                }
+                queue.items.push_back(status);
+                drop(queue);
+                shared.ready.notify_one();
+            }
+        }))?;
+        Ok(Subscription {
+            _guard: guard,
+            shared,
+        })
+    }
+
+    /// Blocks until the next change arrives.
+    pub fn recv(&self) -> Result<Status, Error> {
+        let mut queue = self.shared.lock();
+        loop {
+            if let Some(status) = queue.items.pop_front() {
+                return status;
+            }
+            queue = self
+                .shared
+                .ready
+                .wait(queue)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Blocks until the next change arrives or the timeout elapses.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Result<Status, Error>> {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.shared.lock();
+        loop {
+            if let Some(status) = queue.items.pop_front() {
+                return Some(status);
+            }
+            let timeout = deadline.checked_duration_since(Instant::now())?;
+            queue = self
+                .shared
+                .ready
+                .wait_timeout(queue, timeout)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Returns the next queued change without blocking.
+    pub fn try_recv(&self) -> Option<Result<Status, Error>> {
+        self.shared.lock().items.pop_front()
+    }
+
+    /// Number of changes dropped so far because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// A blocking iterator over changes, which never ends.
+    pub fn events(&self) -> Events<'_, G> {
+        Events { subscription: self }
+    }
+}
+
+/// Blocking iterator returned by [`Subscription::events`].
+pub struct Events<'a, G = Guard> {
+    subscription: &'a Subscription<G>,
+}
+
+impl<G> Iterator for Events<'_, G> {
+    type Item = Result<Status, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.subscription.recv())
+    }
+}
+
+/// Subscribes to power state changes of the system.
+#[cfg(not(target_os = "macos"))]
+pub fn subscribe(options: SubscribeOptions) -> Result<Subscription, Error> {
+    Subscription::with_registration(options, crate::register_power_state_change_callback)
+}
+
+/// Subscribes to power state changes of the system.
+///
+/// Changes are delivered by the main run loop, which must be running, but can be received on
+/// any thread.
+#[cfg(target_os = "macos")]
+pub fn subscribe(
+    mtm: objc2::MainThreadMarker,
+    options: SubscribeOptions,
+) -> Result<Subscription, Error> {
+    Subscription::with_registration(options, |cb| {
+        crate::register_power_state_change_callback(mtm, cb)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::test_util::percentage;
+
+    fn fake_subscription(
+        options: SubscribeOptions,
+    ) -> (Subscription<()>, Arc<Mutex<Option<OnPowerStateChange>>>) {
+        let callback = Arc::new(Mutex::new(None));
+        let subscription = Subscription::with_registration(options, |cb| {
+            *callback.lock().unwrap() = Some(cb);
+            Ok(())
+        })
+        .unwrap();
+        (subscription, callback)
+    }
+
+    fn received(subscription: &Subscription<()>) -> Vec<Option<u8>> {
+        std::iter::from_fn(|| subscription.try_recv())
+            .map(|status| status.unwrap().estimated_energy_percentage)
+            .collect()
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let (subscription, callback) = fake_subscription(SubscribeOptions {
+            capacity: 2,
+            overflow_policy: OverflowPolicy::DropOldest,
+        });
+        let callback = callback.lock().unwrap();
+        let callback = callback.as_ref().unwrap();
+        (1..=3).for_each(|value| callback(percentage(value)));
+
+        assert_eq!(received(&subscription), [Some(2), Some(3)]);
+        assert_eq!(subscription.dropped(), 1);
+    }
+
+    #[test]
+    fn test_keep_latest() {
+        let (subscription, callback) = fake_subscription(SubscribeOptions {
+            capacity: 8,
+            overflow_policy: OverflowPolicy::KeepLatest,
+        });
+        let callback = callback.lock().unwrap();
+        let callback = callback.as_ref().unwrap();
+        (1..=3).for_each(|value| callback(percentage(value)));
+
+        assert_eq!(received(&subscription), [Some(3)]);
+        assert_eq!(subscription.dropped(), 2);
+    }
+
+    #[test]
+    fn test_events_across_threads() {
+        let (subscription, callback) = fake_subscription(SubscribeOptions::default());
+        assert!(
+            subscription
+                .recv_timeout(Duration::from_millis(10))
+                .is_none()
+        );
+
+        let sender = thread::spawn(move || {
+            let callback = callback.lock().unwrap();
+            (1..=3).for_each(|value| callback.as_ref().unwrap()(percentage(value)));
+        });
+        let values = subscription
+            .events()
+            .take(3)
+            .map(|status| status.unwrap().estimated_energy_percentage)
+            .collect::<Vec<_>>();
+        assert_eq!(values, [Some(1), Some(2), Some(3)]);
+        sender.join().unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_subscribe() {
+        let subscription = subscribe(SubscribeOptions::default()).unwrap();
+        drop(subscription);
+    }
+}
@@ -0,0 +1,20 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{Error, PowerState, Status};
+
+pub(crate) fn status(power_state: PowerState, percentage: u8) -> Status {
+    Status {
+        power_state,
+        estimated_energy_percentage: Some(percentage),
+        ..Status::default()
+    }
+}
+
+pub(crate) fn percentage(value: u8) -> Result<Status, Error> {
+    Ok(status(PowerState::Unknown, value))
+}
+
+/// An error standing in for a failed status query, as no variant is shared by all platforms.
+pub(crate) fn query_failed() -> Error {
+    Error::CallbackThreadSpawnFailed(std::io::Error::other("simulated failure"))
+}
@@ -0,0 +1,341 @@
+use std::{
+    panic,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    BackendGuard, Capabilities, Error, OnPowerStateChange, PowerBackend, Status, WatchOptions,
+    watch,
+};
+
+#[derive(Default)]
+struct MockState {
+    status: Status,
+    subscribers: Vec<(u64, Arc<OnPowerStateChange>)>,
+    next_id: u64,
+}
+
+/// A [`PowerBackend`] driven by the test instead of the OS.
+///
+/// Pushed statuses and errors are delivered synchronously on the pushing thread, filtered by the
+/// [`WatchOptions`] of each subscriber but never coalesced. Clones share the same state.
+#[derive(Clone)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+    capabilities: Capabilities,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new(Status::default())
+    }
+}
+
+impl MockBackend {
+    /// Creates a backend reporting `status` until another one is pushed.
+    pub fn new(status: Status) -> Self {
+        MockBackend {
+            state: Arc::new(Mutex::new(MockState {
+                status,
+                ..Default::default()
+            })),
+            capabilities: Capabilities::all(),
+        }
+    }
+
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn subscribers(&self) -> Vec<Arc<OnPowerStateChange>> {
+        self.state()
+            .subscribers
+            .iter()
+            .map(|(_, cb)| cb.clone())
+            .collect()
+    }
+
+    /// Makes `status` the current status and delivers it to all subscribers.
+    pub fn push_status(&self, status: Status) {
+        self.state().status = status.clone();
+        for cb in self.subscribers() {
+            let status = status.clone();
+            let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| cb(Ok(status))));
+        }
+    }
+
+    /// Delivers an error to all subscribers, keeping the current status.
+    ///
+    /// Errors can't be cloned, so `error` is called once per subscriber.
+    pub fn push_error<E>(&self, error: E)
+    where
+        E: Fn() -> Error,
+    {
+        for cb in self.subscribers() {
+            let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| cb(Err(error()))));
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.state().subscribers.len()
+    }
+}
+
+struct MockSubscription {
+    state: Arc<Mutex<MockState>>,
+    id: u64,
+}
+
+impl Drop for MockSubscription {
+    fn drop(&mut self) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .subscribers
+            .retain(|(id, _)| *id != self.id);
+    }
+}
+
+impl PowerBackend for MockBackend {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn status(&self) -> Result<Status, Error> {
+        Ok(self.state().status.clone())
+    }
+
+    fn subscribe(
+        &self,
+        options: WatchOptions,
+        cb: OnPowerStateChange,
+    ) -> Result<BackendGuard, Error> {
+        let cb = watch::filter(&options, || self.status(), cb);
+        let mut state = self.state();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.subscribers.push((id, Arc::new(cb)));
+        Ok(BackendGuard::new(MockSubscription {
+            state: self.state.clone(),
+            id,
+        }))
+    }
+}
+
+enum TimelineEvent {
+    Status(Status),
+    Error(Box<dyn Fn() -> Error + Send>),
+}
+
+/// A script of statuses and errors pushed to a [`MockBackend`] at given points of virtual time.
+///
+/// Time only moves when the timeline is advanced, so scripts spanning hours run instantly, e.g.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use powerstate::{MockBackend, PowerState, Status, Timeline};
+/// let status = |power_state, percentage| Status {
+///     power_state,
+///     estimated_energy_percentage: Some(percentage),
+///     ..Status::default()
+/// };
+/// let mut timeline = Timeline::new(MockBackend::default())
+///     .status_at(Duration::ZERO, status(PowerState::AC, 100))
+///     .status_at(Duration::from_secs(5), status(PowerState::Battery, 100))
+///     .status_at(Duration::from_secs(60), status(PowerState::Battery, 20));
+/// // Pushes the first two statuses.
+/// assert_eq!(timeline.advance(Duration::from_secs(10)), 2);
+/// ```
+pub struct Timeline {
+    backend: MockBackend,
+    // Sorted by time, events at the same time in the order they were added.
+    events: Vec<(Duration, TimelineEvent)>,
+    next: usize,
+    now: Duration,
+}
+
+impl Timeline {
+    /// Creates an empty timeline at virtual time zero.
+    pub fn new(backend: MockBackend) -> Self {
+        Timeline {
+            backend,
+            events: vec![],
+            next: 0,
+            now: Duration::ZERO,
+        }
+    }
+
+    fn insert(&mut self, at: Duration, event: TimelineEvent) {
+        let index = self.events.partition_point(|(time, _)| *time <= at);
+        // Events in the past would never be pushed.
+        let index = index.max(self.next);
+        self.events.insert(index, (at.max(self.now), event));
+    }
+
+    /// Pushes `status` at virtual time `at`.
+    pub fn status_at(mut self, at: Duration, status: Status) -> Self {
+        self.insert(at, TimelineEvent::Status(status));
+        self
+    }
+
+    /// Pushes the error returned by `error` at virtual time `at`.
+    pub fn error_at<E>(mut self, at: Duration, error: E) -> Self
+    where
+        E: Fn() -> Error + Send + 'static,
+    {
+        self.insert(at, TimelineEvent::Error(Box::new(error)));
+        self
+    }
+
+    pub fn backend(&self) -> &MockBackend {
+        &self.backend
+    }
+
+    /// The current virtual time.
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// The virtual time of the next event, if any is left.
+    pub fn next_event_at(&self) -> Option<Duration> {
+        self.events.get(self.next).map(|(at, _)| *at)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next == self.events.len()
+    }
+
+    /// Moves virtual time forward by `duration`, pushing the events up to and including the new
+    /// time. Returns the number of events pushed.
+    pub fn advance(&mut self, duration: Duration) -> usize {
+        self.advance_to(self.now + duration)
+    }
+
+    /// Moves virtual time forward to `at`, see [`Timeline::advance`].
+    pub fn advance_to(&mut self, at: Duration) -> usize {
+        let start = self.next;
+        while let Some((time, event)) = self.events.get(self.next)
+            && *time <= at
+        {
+            self.now = *time;
+            self.next += 1;
+            match event {
+                TimelineEvent::Status(status) => self.backend.push_status(status.clone()),
+                TimelineEvent::Error(error) => self.backend.push_error(error),
+            }
+        }
+        self.now = self.now.max(at);
+        self.next - start
+    }
+
+    /// Pushes the next event, moving virtual time to it. Returns its time, or `None` when the
+    /// timeline is finished.
+    pub fn step(&mut self) -> Option<Duration> {
+        let at = self.next_event_at()?;
+        self.advance_to(at);
+        Some(at)
+    }
+
+    /// Pushes all remaining events, returning the number pushed.
+    pub fn run(&mut self) -> usize {
+        match self.events.last() {
+            Some((at, _)) => self.advance_to(*at),
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::{
+        PowerMonitor, PowerState,
+        test_util::{query_failed, status},
+    };
+
+    #[test]
+    fn test_mock_backend() {
+        let backend = MockBackend::new(status(PowerState::AC, 80));
+        let (tx, rx) = mpsc::channel();
+        let monitor = PowerMonitor::builder()
+            .backend(backend.clone())
+            .on_change(move |status| {
+                let _ = tx.send(status.as_ref().ok().map(|s| s.power_state));
+            })
+            .start()
+            .unwrap();
+        assert_eq!(monitor.status().unwrap().power_state, PowerState::AC);
+        assert_eq!(backend.subscriber_count(), 1);
+
+        backend.push_status(status(PowerState::Battery, 80));
+        backend.push_error(query_failed);
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            [Some(PowerState::Battery), None]
+        );
+        assert_eq!(backend.status().unwrap().power_state, PowerState::Battery);
+
+        drop(monitor);
+        assert_eq!(backend.subscriber_count(), 0);
+    }
+
+    #[test]
+    fn test_timeline() {
+        let backend = MockBackend::default();
+        let (tx, rx) = mpsc::channel();
+        let _guard = backend
+            .subscribe(
+                WatchOptions {
+                    percentage_step: Some(10),
+                    ..WatchOptions::default()
+                },
+                Box::new(move |status| {
+                    let _ = tx.send(status.map(|s| s.estimated_energy_percentage));
+                }),
+            )
+            .unwrap();
+
+        let mut timeline = Timeline::new(backend)
+            .status_at(Duration::from_secs(60), status(PowerState::Battery, 20))
+            .status_at(Duration::ZERO, status(PowerState::AC, 25))
+            .status_at(Duration::from_secs(5), status(PowerState::Battery, 25))
+            .error_at(Duration::from_secs(30), query_failed);
+        assert_eq!(timeline.next_event_at(), Some(Duration::ZERO));
+
+        assert_eq!(timeline.advance(Duration::from_secs(10)), 2);
+        assert_eq!(timeline.now(), Duration::from_secs(10));
+        assert_eq!(
+            rx.try_iter().map(Result::unwrap).collect::<Vec<_>>(),
+            [Some(25), Some(25)]
+        );
+
+        assert_eq!(timeline.step(), Some(Duration::from_secs(30)));
+        assert!(rx.try_recv().unwrap().is_err());
+
+        // Moving by 5 points is below the step of 10.
+        assert_eq!(timeline.run(), 1);
+        assert!(timeline.is_finished());
+        assert_eq!(timeline.now(), Duration::from_secs(60));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(
+            timeline
+                .backend()
+                .status()
+                .unwrap()
+                .estimated_energy_percentage,
+            Some(20)
+        );
+    }
+}
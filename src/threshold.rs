@@ -0,0 +1,331 @@
+use std::{mem, sync::Mutex};
+
+use crate::{BatteryState, ChangedFields, Error, Guard, PowerState, Status};
+
+/// Hysteresis used by [`ThresholdWatcher::default`], in percentage points.
+pub const DEFAULT_HYSTERESIS: u8 = 2;
+
+/// When a [`Threshold`] is crossed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThresholdDirection {
+    /// The level drops to or below the threshold while discharging.
+    Falling,
+    /// The level rises to or above the threshold while charging.
+    Rising,
+}
+
+/// A named battery level to be notified about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Threshold {
+    pub name: String,
+    /// Battery level, in range [0, 100].
+    pub percentage: u8,
+    pub direction: ThresholdDirection,
+}
+
+impl Threshold {
+    /// Crossed when the level drops to `percentage` or below while discharging.
+    pub fn below(name: impl Into<String>, percentage: u8) -> Self {
+        Threshold {
+            name: name.into(),
+            percentage,
+            direction: ThresholdDirection::Falling,
+        }
+    }
+
+    /// Crossed when the level reaches `percentage` or above while charging.
+    pub fn above(name: impl Into<String>, percentage: u8) -> Self {
+        Threshold {
+            name: name.into(),
+            percentage,
+            direction: ThresholdDirection::Rising,
+        }
+    }
+
+    fn is_crossed(&self, status: &Status, percentage: u8) -> bool {
+        match self.direction {
+            ThresholdDirection::Falling => {
+                is_in(status, BatteryState::Discharging, PowerState::Battery)
+                    && percentage <= self.percentage
+            }
+            ThresholdDirection::Rising => {
+                is_in(status, BatteryState::Charging, PowerState::AC)
+                    && percentage >= self.percentage
+            }
+        }
+    }
+
+    /// Whether the level moved back far enough past the threshold to report it again.
+    fn is_rearmed(&self, percentage: u8, hysteresis: u8) -> bool {
+        match self.direction {
+            ThresholdDirection::Falling => percentage > self.percentage.saturating_add(hysteresis),
+            ThresholdDirection::Rising => percentage < self.percentage.saturating_sub(hysteresis),
+        }
+    }
+}
+
+/// Whether some battery is in `state`, or the system in `power_state` when no battery is reported.
+///
+/// Batteries on AC aren't necessarily charging, e.g. when full or held at a charge limit.
+fn is_in(status: &Status, state: BatteryState, power_state: PowerState) -> bool {
+    if status.batteries.is_empty() {
+        status.power_state == power_state
+    } else {
+        status
+            .batteries
+            .iter()
+            .any(|battery| battery.state == state)
+    }
+}
+
+/// A [`Threshold`] that was crossed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThresholdEvent {
+    pub threshold: Threshold,
+    /// Battery level when the threshold was crossed.
+    pub percentage: u8,
+}
+
+/// Reports battery levels crossing thresholds, once per crossing.
+///
+/// A threshold that was reported is only reported again after the level moved back past it by
+/// more than the hysteresis, so a level jittering around the boundary is reported once.
+#[derive(Debug, Clone)]
+pub struct ThresholdWatcher {
+    thresholds: Vec<(Threshold, bool)>,
+    hysteresis: u8,
+}
+
+impl Default for ThresholdWatcher {
+    fn default() -> Self {
+        Self::new(DEFAULT_HYSTERESIS)
+    }
+}
+
+impl ThresholdWatcher {
+    /// Creates a watcher without thresholds, using `hysteresis` percentage points.
+    pub fn new(hysteresis: u8) -> Self {
+        ThresholdWatcher {
+            thresholds: vec![],
+            hysteresis,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: Threshold) -> Self {
+        self.add_threshold(threshold);
+        self
+    }
+
+    pub fn add_threshold(&mut self, threshold: Threshold) {
+        self.thresholds.push((threshold, true));
+    }
+
+    pub fn thresholds(&self) -> impl Iterator<Item = &Threshold> {
+        self.thresholds.iter().map(|(threshold, _)| threshold)
+    }
+
+    /// Returns the thresholds crossed by `status`, in the order they were added.
+    pub fn update(&mut self, status: &Status) -> Vec<ThresholdEvent> {
+        let Some(percentage) = status.estimated_energy_percentage else {
+            return vec![];
+        };
+        let mut events = vec![];
+        for (threshold, armed) in &mut self.thresholds {
+            if *armed {
+                if threshold.is_crossed(status, percentage) {
+                    *armed = false;
+                    events.push(ThresholdEvent {
+                        threshold: threshold.clone(),
+                        percentage,
+                    });
+                }
+            } else if threshold.is_rearmed(percentage, self.hysteresis) {
+                *armed = true;
+            }
+        }
+        events
+    }
+}
+
+/// Returns a status change callback that feeds `watcher` and reports crossed thresholds.
+fn threshold_callback<F>(
+    watcher: ThresholdWatcher,
+    cb: F,
+) -> impl Fn(Result<crate::StatusChange, Error>) + Send + Sync + 'static
+where
+    F: Fn(ThresholdEvent) + Send + Sync + 'static,
+{
+    let watcher = Mutex::new((watcher, false));
+    move |change| match change {
+        Ok(change) => {
+            let mut events = vec![];
+            {
+                let mut guard = watcher.lock().unwrap_or_else(|e| e.into_inner());
+                let (watcher, seeded) = &mut *guard;
+                // Thresholds already crossed at registration time are reported with the first
+                // change, which carries the status at that time.
+                if !mem::replace(seeded, true) {
+                    events.extend(watcher.update(&change.previous));
+                }
+                if change.changed.intersects(
+                    ChangedFields::POWER_STATE
+                        | ChangedFields::PERCENTAGE
+                        | ChangedFields::BATTERY_STATE,
+                ) {
+                    events.extend(watcher.update(&change.current));
+                }
+            }
+            events.into_iter().for_each(&cb);
+        }
+        Err(e) => log::warn!("failed to get power state for threshold alerts: {e}"),
+    }
+}
+
+/// Invokes the callback whenever the battery level crosses one of the thresholds of `watcher`.
+#[cfg(not(target_os = "macos"))]
+pub fn register_threshold_callback<F>(watcher: ThresholdWatcher, cb: F) -> Result<Guard, Error>
+where
+    F: Fn(ThresholdEvent) + Send + Sync + 'static,
+{
+    crate::register_status_change_callback(threshold_callback(watcher, cb))
+}
+
+/// Invokes the callback whenever the battery level crosses one of the thresholds of `watcher`.
+#[cfg(target_os = "macos")]
+pub fn register_threshold_callback<F>(
+    mtm: objc2::MainThreadMarker,
+    watcher: ThresholdWatcher,
+    cb: F,
+) -> Result<Guard, Error>
+where
+    F: Fn(ThresholdEvent) + Send + Sync + 'static,
+{
+    crate::register_status_change_callback(mtm, threshold_callback(watcher, cb))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::{BatteryInfo, ChangeTracker, test_util::status};
+
+    fn run(watcher: &mut ThresholdWatcher, script: &[(PowerState, u8)]) -> Vec<(String, u8)> {
+        script
+            .iter()
+            .flat_map(|(power_state, percentage)| {
+                watcher.update(&status(*power_state, *percentage))
+            })
+            .map(|event| (event.threshold.name, event.percentage))
+            .collect()
+    }
+
+    #[test]
+    fn test_falling_thresholds_with_hysteresis() {
+        let mut watcher = ThresholdWatcher::new(2)
+            .with_threshold(Threshold::below("low", 20))
+            .with_threshold(Threshold::below("critical", 10))
+            .with_threshold(Threshold::below("empty", 5));
+
+        use PowerState::*;
+        let events = run(
+            &mut watcher,
+            &[
+                (Battery, 25),
+                (Battery, 20),
+                // Jitter around the boundary is not reported again.
+                (Battery, 21),
+                (Battery, 20),
+                (Battery, 22),
+                (Battery, 19),
+                // Not while charging.
+                (AC, 9),
+                (Battery, 9),
+                (Battery, 4),
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                ("low".to_string(), 20),
+                ("critical".to_string(), 9),
+                ("empty".to_string(), 4)
+            ]
+        );
+
+        // Rearmed after charging past the hysteresis.
+        let events = run(&mut watcher, &[(AC, 23), (Battery, 20)]);
+        assert_eq!(events, [("low".to_string(), 20)]);
+    }
+
+    #[test]
+    fn test_rising_threshold() {
+        let mut watcher = ThresholdWatcher::default().with_threshold(Threshold::above("full", 80));
+
+        use PowerState::*;
+        let events = run(
+            &mut watcher,
+            &[(AC, 79), (Battery, 85), (AC, 80), (AC, 79), (AC, 81)],
+        );
+        assert_eq!(events, [("full".to_string(), 80)]);
+
+        let events = run(&mut watcher, &[(Battery, 77), (AC, 80)]);
+        assert_eq!(events, [("full".to_string(), 80)]);
+    }
+
+    #[test]
+    fn test_unknown_percentage_is_ignored() {
+        let mut watcher = ThresholdWatcher::default().with_threshold(Threshold::below("low", 20));
+        let status = Status {
+            power_state: PowerState::Battery,
+            ..Status::default()
+        };
+        assert!(watcher.update(&status).is_empty());
+    }
+
+    #[test]
+    fn test_battery_states() {
+        let mut watcher = ThresholdWatcher::default()
+            .with_threshold(Threshold::above("full", 80))
+            .with_threshold(Threshold::below("low", 20));
+        let with_battery = |power_state, percentage, state| Status {
+            batteries: vec![BatteryInfo {
+                state,
+                ..Default::default()
+            }],
+            ..status(power_state, percentage)
+        };
+
+        // Held at a charge limit on AC.
+        let held = with_battery(PowerState::AC, 85, BatteryState::NotCharging);
+        assert!(watcher.update(&held).is_empty());
+        let charging = with_battery(PowerState::AC, 86, BatteryState::Charging);
+        assert_eq!(watcher.update(&charging).len(), 1);
+
+        // Discharging on AC, e.g. with an underpowered charger.
+        let discharging = with_battery(PowerState::AC, 15, BatteryState::Discharging);
+        assert_eq!(watcher.update(&discharging)[0].threshold.name, "low");
+    }
+
+    #[test]
+    fn test_callback_seeded_by_first_change() {
+        let (tx, rx) = mpsc::channel();
+        let watcher = ThresholdWatcher::default().with_threshold(Threshold::below("low", 20));
+        let callback = threshold_callback(watcher, move |event| {
+            let _ = tx.send((event.threshold.name, event.percentage));
+        });
+        assert!(rx.try_recv().is_err());
+
+        // Only the saver mode changed, but the initial level is already below the threshold.
+        let mut tracker = ChangeTracker::new(status(PowerState::Battery, 15));
+        let change = tracker.update(Status {
+            power_saving_mode: true,
+            ..status(PowerState::Battery, 15)
+        });
+        callback(Ok(change.unwrap()));
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [("low".to_string(), 15)]);
+
+        callback(Ok(tracker.update(status(PowerState::Battery, 14)).unwrap()));
+        assert!(rx.try_recv().is_err());
+    }
+}
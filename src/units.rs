@@ -0,0 +1,212 @@
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, Sub},
+    time::Duration,
+};
+
+/// Writes `value` with the precision of the formatter, one decimal by default.
+fn fmt_quantity(f: &mut fmt::Formatter<'_>, value: f32, unit: &str) -> fmt::Result {
+    let precision = f.precision().unwrap_or(1);
+    write!(f, "{value:.precision$} {unit}")
+}
+
+/// An amount of energy, stored in joules.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Energy(f32);
+
+impl Energy {
+    pub const fn from_joules(joules: f32) -> Self {
+        Energy(joules)
+    }
+
+    pub const fn from_watt_hours(watt_hours: f32) -> Self {
+        Energy(watt_hours * 3600.0)
+    }
+
+    pub const fn joules(self) -> f32 {
+        self.0
+    }
+
+    pub const fn watt_hours(self) -> f32 {
+        self.0 / 3600.0
+    }
+
+    /// How long it takes to deliver this energy at `rate`, or `None` if the rate isn't positive.
+    pub fn time_at(self, rate: Power) -> Option<Duration> {
+        if rate.0 <= 0.0 {
+            return None;
+        }
+        Duration::try_from_secs_f32(self.0.max(0.0) / rate.0).ok()
+    }
+}
+
+impl Add for Energy {
+    type Output = Energy;
+
+    fn add(self, rhs: Energy) -> Energy {
+        Energy(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Energy {
+    type Output = Energy;
+
+    fn sub(self, rhs: Energy) -> Energy {
+        Energy(self.0 - rhs.0)
+    }
+}
+
+impl Sum for Energy {
+    fn sum<I: Iterator<Item = Energy>>(iter: I) -> Energy {
+        iter.fold(Energy::default(), Add::add)
+    }
+}
+
+/// Formatted in watt-hours, e.g. `52.3 Wh`.
+impl fmt::Display for Energy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_quantity(f, self.watt_hours(), "Wh")
+    }
+}
+
+/// A rate of energy transfer, stored in watts.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Power(f32);
+
+impl Power {
+    pub const fn from_watts(watts: f32) -> Self {
+        Power(watts)
+    }
+
+    pub const fn watts(self) -> f32 {
+        self.0
+    }
+}
+
+impl Add for Power {
+    type Output = Power;
+
+    fn add(self, rhs: Power) -> Power {
+        Power(self.0 + rhs.0)
+    }
+}
+
+impl Sum for Power {
+    fn sum<I: Iterator<Item = Power>>(iter: I) -> Power {
+        iter.fold(Power::default(), Add::add)
+    }
+}
+
+impl fmt::Display for Power {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_quantity(f, self.0, "W")
+    }
+}
+
+/// An electric potential, stored in volts.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Voltage(f32);
+
+impl Voltage {
+    pub const fn from_volts(volts: f32) -> Self {
+        Voltage(volts)
+    }
+
+    pub const fn volts(self) -> f32 {
+        self.0
+    }
+}
+
+impl fmt::Display for Voltage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_quantity(f, self.0, "V")
+    }
+}
+
+/// A temperature, stored in kelvin.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Temperature(f32);
+
+impl Temperature {
+    const ZERO_CELSIUS: f32 = 273.15;
+
+    pub const fn from_kelvin(kelvin: f32) -> Self {
+        Temperature(kelvin)
+    }
+
+    pub const fn from_celsius(celsius: f32) -> Self {
+        Temperature(celsius + Self::ZERO_CELSIUS)
+    }
+
+    pub const fn kelvin(self) -> f32 {
+        self.0
+    }
+
+    pub const fn celsius(self) -> f32 {
+        self.0 - Self::ZERO_CELSIUS
+    }
+}
+
+/// Formatted in degrees Celsius, e.g. `31.0 °C`.
+impl fmt::Display for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_quantity(f, self.celsius(), "°C")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        let energy = Energy::from_watt_hours(50.0);
+        assert_eq!(energy.joules(), 180_000.0);
+        assert_eq!(energy.watt_hours(), 50.0);
+        assert_eq!(
+            [energy, Energy::from_joules(36_000.0)]
+                .into_iter()
+                .sum::<Energy>()
+                .watt_hours(),
+            60.0
+        );
+        assert_eq!(
+            energy.time_at(Power::from_watts(10.0)),
+            Some(Duration::from_secs(5 * 3600))
+        );
+        assert_eq!(energy.time_at(Power::default()), None);
+
+        let temperature = Temperature::from_celsius(30.0);
+        assert!((temperature.kelvin() - 303.15).abs() < 0.01);
+        assert!((temperature.celsius() - 30.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Energy::from_watt_hours(52.26).to_string(), "52.3 Wh");
+        assert_eq!(format!("{:.2}", Power::from_watts(7.5)), "7.50 W");
+        assert_eq!(Voltage::from_volts(12.0).to_string(), "12.0 V");
+        assert_eq!(Temperature::from_kelvin(304.15).to_string(), "31.0 °C");
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::Mutex;
+
+use crate::{ChangedFields, CoalesceOptions, Error, Status};
+
+/// Selects which changes trigger a power state change callback.
+///
+/// Backends only subscribe to the OS notifications needed for the selected changes.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Switching between AC and battery, power sources being connected or disconnected, and
+    /// batteries starting or stopping to charge.
+    pub power_source: bool,
+    /// Report the estimated percentage once it moved by at least this many points since the
+    /// last callback, or never when `None`.
+    pub percentage_step: Option<u8>,
+    pub time_remaining: bool,
+    pub saver_mode: bool,
+    /// Batteries being added or removed.
+    pub battery_hotplug: bool,
+    /// Merges bursts of notifications, off by default.
+    ///
+    /// When set, the callback runs on a separate thread instead of the thread of the backend,
+    /// e.g. the main run loop on macOS.
+    pub coalesce: Option<CoalesceOptions>,
+}
+
+impl Default for WatchOptions {
+    /// Watches every kind of change.
+    fn default() -> Self {
+        WatchOptions {
+            power_source: true,
+            percentage_step: Some(1),
+            time_remaining: true,
+            saver_mode: true,
+            battery_hotplug: true,
+            coalesce: None,
+        }
+    }
+}
+
+impl WatchOptions {
+    /// Watches nothing, to be combined with the kinds of change of interest, e.g.
+    /// `WatchOptions { saver_mode: true, ..WatchOptions::none() }`.
+    pub fn none() -> Self {
+        WatchOptions {
+            power_source: false,
+            percentage_step: None,
+            time_remaining: false,
+            saver_mode: false,
+            battery_hotplug: false,
+            coalesce: None,
+        }
+    }
+
+    /// Whether changes of the power supplies or batteries are watched, as opposed to only
+    /// power saving mode.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub(crate) fn watches_power_supplies(&self) -> bool {
+        self.power_source
+            || self.percentage_step.is_some()
+            || self.time_remaining
+            || self.battery_hotplug
+    }
+
+    fn watches_everything(&self) -> bool {
+        self.power_source
+            && self.percentage_step.is_some_and(|step| step <= 1)
+            && self.time_remaining
+            && self.saver_mode
+            && self.battery_hotplug
+    }
+
+    fn fields(&self) -> ChangedFields {
+        let mut fields = ChangedFields::empty();
+        if self.power_source {
+            fields |= ChangedFields::POWER_STATE
+                | ChangedFields::POWER_SOURCES
+                | ChangedFields::BATTERY_STATE;
+        }
+        fields.set(ChangedFields::TIME_REMAINING, self.time_remaining);
+        fields.set(ChangedFields::SAVER_MODE, self.saver_mode);
+        fields.set(ChangedFields::BATTERY_SET, self.battery_hotplug);
+        fields
+    }
+
+    /// Whether `current` differs from the `last` reported status in a watched way.
+    fn accepts(&self, last: &Status, current: &Status) -> bool {
+        let percentage_changed = match (
+            self.percentage_step,
+            last.estimated_energy_percentage,
+            current.estimated_energy_percentage,
+        ) {
+            (None, _, _) => false,
+            (Some(step), Some(a), Some(b)) => a.abs_diff(b) >= step.max(1),
+            (Some(_), a, b) => a != b,
+        };
+        percentage_changed || ChangedFields::between(last, current).intersects(self.fields())
+    }
+}
+
+/// Wraps `cb` so that it is only invoked for the changes selected by `options`, starting from
+/// the status returned by `current`.
+pub(crate) fn filter<S, F>(
+    options: &WatchOptions,
+    current: S,
+    cb: F,
+) -> Box<dyn Fn(Result<Status, Error>) + Send + Sync>
+where
+    S: FnOnce() -> Result<Status, Error>,
+    F: Fn(Result<Status, Error>) + Send + Sync + 'static,
+{
+    if options.watches_everything() {
+        return Box::new(cb);
+    }
+
+    let options = options.clone();
+    // Changes are compared against the status at registration time.
+    let last = Mutex::new(current().unwrap_or_default());
+    Box::new(move |status| match status {
+        Ok(status) => {
+            let mut last = last.lock().unwrap_or_else(|e| e.into_inner());
+            if options.accepts(&last, &status) {
+                *last = status.clone();
+                drop(last);
+                cb(Ok(status));
+            }
+        }
+        Err(e) => cb(Err(e)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PowerState, test_util};
+
+    fn status(power_state: PowerState, percentage: u8, power_saving_mode: bool) -> Status {
+        Status {
+            power_saving_mode,
+            ..test_util::status(power_state, percentage)
+        }
+    }
+
+    #[test]
+    fn test_power_source_only() {
+        let options = WatchOptions {
+            power_source: true,
+            ..WatchOptions::none()
+        };
+        assert!(options.watches_power_supplies());
+        let last = status(PowerState::AC, 50, false);
+        assert!(!options.accepts(&last, &status(PowerState::AC, 40, true)));
+        assert!(options.accepts(&last, &status(PowerState::Battery, 50, false)));
+    }
+
+    #[test]
+    fn test_saver_mode_only() {
+        let options = WatchOptions {
+            saver_mode: true,
+            ..WatchOptions::none()
+        };
+        assert!(!options.watches_power_supplies());
+        let last = status(PowerState::AC, 50, false);
+        assert!(!options.accepts(&last, &status(PowerState::Battery, 40, false)));
+        assert!(options.accepts(&last, &status(PowerState::AC, 50, true)));
+    }
+
+    #[test]
+    fn test_percentage_step() {
+        let options = WatchOptions {
+            percentage_step: Some(5),
+            ..WatchOptions::none()
+        };
+        let last = status(PowerState::Battery, 50, false);
+        assert!(!options.accepts(&last, &status(PowerState::Battery, 46, false)));
+        assert!(options.accepts(&last, &status(PowerState::Battery, 45, false)));
+        assert!(options.accepts(&last, &Status::default()));
+    }
+}